@@ -0,0 +1,39 @@
+// Generates the parser's tables from src/parser/cool.grammar.
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+#[path = "src/parser/generator/mod.rs"]
+mod generator;
+
+const GRAMMAR: &str = "src/parser/cool.grammar";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", GRAMMAR);
+    println!("cargo:rerun-if-changed=src/parser/generator/mod.rs");
+
+    let mut text = String::new();
+    File::open(GRAMMAR)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", GRAMMAR, err));
+
+    let grammar = generator::parse_grammar(&text).unwrap_or_else(|err| {
+        eprintln!("{}: {}", GRAMMAR, err);
+        process::exit(1);
+    });
+    let tables = generator::build_tables(&grammar).unwrap_or_else(|conflicts| {
+        for conflict in &conflicts {
+            eprintln!("{}: {}", GRAMMAR, conflict);
+        }
+        eprintln!("{}: {} unresolved conflicts", GRAMMAR, conflicts.len());
+        process::exit(1);
+    });
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("cool_parser.rs");
+    File::create(&out)
+        .and_then(|mut f| f.write_all(generator::write_parser(&grammar, &tables).as_bytes()))
+        .unwrap_or_else(|err| panic!("cannot write {}: {}", out.display(), err));
+}
@@ -1,67 +1,136 @@
-use std::rc::Rc;
+use std::fmt;
+
+use source::Span;
+use symbol;
 /*
  * Structs and enums for the various parts of the Cool Language.
  */
-pub type Symbol = Rc<String>;
-pub type CoolType = Rc<String>;
+pub use symbol::Symbol;
+
+// A type written in the program. SELF_TYPE isn't a class: it stands for
+// the type of `self` wherever it appears, which the manual writes as
+// SELF_TYPE_C inside class C.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CoolType {
+    Named(Symbol),
+    SelfType
+}
+
+impl CoolType {
+    pub fn new(name: Symbol) -> CoolType {
+        if name == symbol::SELF_TYPE {
+            CoolType::SelfType
+        } else {
+            CoolType::Named(name)
+        }
+    }
+
+    // The name the type is written with.
+    pub fn name(&self) -> Symbol {
+        match *self {
+            CoolType::Named(name) => name,
+            CoolType::SelfType => symbol::SELF_TYPE
+        }
+    }
+}
+
+impl fmt::Display for CoolType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
+#[derive(Debug, PartialEq)]
 pub struct Program {
-    pub classes: Vec<Box<Class>>
+    pub classes: Vec<Class>,
+    pub span: Span
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Class {
     pub name: Symbol,
     pub parent: Symbol,
-    pub features: Vec<Box<Feature>>
+    pub features: Vec<Feature>,
+    pub span: Span
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Feature {
     Method {
         name: Symbol,
         params: Vec<Formal>,
         return_type: CoolType,
-        body: Box<Expression>
+        body: Box<Expression>,
+        span: Span
     },
     Attribute {
         name: Symbol,
         cool_type: CoolType,
-        expr: Box<Expression>
+        expr: Box<Expression>,
+        span: Span
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Formal {
     pub name: Symbol,
-    pub cool_type: CoolType
+    pub cool_type: CoolType,
+    pub span: Span
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+    // Filled in by the type checker; None until then.
+    pub static_type: Option<CoolType>
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Expression {
+        Expression { kind, span, static_type: None }
+    }
 }
 
-pub enum Expression {
+#[derive(Debug, PartialEq)]
+pub enum ExpressionKind {
     Assign(Symbol, Box<Expression>),
-    Dispatch(Box<Expression>, Symbol, Vec<Box<Expression>>),
-    StaticDispatch(Box<Expression>, Symbol, Symbol, Vec<Box<Expression>>),
+    Dispatch(Box<Expression>, Symbol, Vec<Expression>),
+    StaticDispatch(Box<Expression>, CoolType, Symbol, Vec<Expression>),
     If(Box<Expression>, Box<Expression>, Box<Expression>),
     While(Box<Expression>, Box<Expression>),
+    // A let with a single binding: the name, its type, its initializer
+    // (NoExpr if there isn't one) and the body. Like the reference parser,
+    // we read `let a : A, b : B <- e in body` as
+    // `let a : A in let b : B <- e in body`, so each binding after the
+    // first is a Let that is the body of the one before it. The inner
+    // Lets' spans start at the comma before their binding.
     Let(Symbol, CoolType, Box<Expression>, Box<Expression>),
-    Case(Box<Expression>, Vec<Box<CaseBranch>>),
-    Block(Vec<Box<Expression>>),
+    Case(Box<Expression>, Vec<CaseBranch>),
+    Block(Vec<Expression>),
     New(CoolType),
     IsVoid(Box<Expression>),
     BinaryOperation(BinOp, Box<Expression>, Box<Expression>),
     Negation(Box<Expression>),
     Not(Box<Expression>),
     Identifier(Symbol),
-    IntLiteral(i32),
+    // The digits as written, in the int table.
+    IntLiteral(Symbol),
     StringLiteral(Symbol),
     True,
     False,
     NoExpr
 }
 
+#[derive(Debug, PartialEq)]
 pub struct CaseBranch {
     pub name: Symbol,
     pub cool_type: CoolType,
-    pub expr: Box<Expression>
+    pub expr: Box<Expression>,
+    pub span: Span
 }
 
+#[derive(Debug, PartialEq)]
 pub enum BinOp {
     Plus,
     Minus,
@@ -71,3 +140,19 @@ pub enum BinOp {
     LessThanEqual,
     Equal
 }
+
+// The tree with its spans left out, for tests that compare trees read
+// from differently laid out text.
+#[cfg(test)]
+pub fn without_spans(program: &Program) -> String {
+    let tree = format!("{:?}", program);
+    let mut stripped = String::new();
+    let mut rest = &tree[..];
+    while let Some(start) = rest.find("span: Span {") {
+        stripped.push_str(&rest[.. start]);
+        let end = rest[start ..].find('}').unwrap();
+        rest = &rest[start + end + 1 ..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
@@ -0,0 +1,139 @@
+/*
+ * Code generation for SPIM, laying objects out the way the reference
+ * compiler and its runtime expect.
+ *
+ * So far this only emits the program's constants: an Int object for every
+ * entry of the int table, a String object for every entry of the string
+ * table, and the two Bools. Each object starts with its class tag, its size
+ * in words and its dispatch table, preceded by the -1 the garbage collector
+ * looks for.
+ */
+
+use std::io;
+use std::io::Write;
+
+use symbol::{Symbol, Table};
+
+// The basic classes' tags, which the runtime reads from _int_tag and
+// friends. They follow the order the class table is built in.
+const INT_TAG: u32 = 2;
+const BOOL_TAG: u32 = 3;
+const STRING_TAG: u32 = 4;
+
+// Every object has a tag, a size and a dispatch table before its fields.
+const HEADER_WORDS: usize = 3;
+
+pub fn emit_constants(out: &mut dyn Write) -> io::Result<()> {
+    // Default values, which the code for new objects points at.
+    Symbol::int("0");
+    Symbol::string("");
+
+    writeln!(out, "\t.data")?;
+    writeln!(out, "\t.align\t2")?;
+    for &(name, tag) in &[("_int_tag", INT_TAG), ("_bool_tag", BOOL_TAG), ("_string_tag", STRING_TAG)] {
+        writeln!(out, "\t.globl\t{}", name)?;
+        writeln!(out, "{}:", name)?;
+        writeln!(out, "\t.word\t{}", tag)?;
+    }
+
+    // Strings go first, since each one adds its length to the int table.
+    for string in Table::Str.entries() {
+        let text = string.as_str();
+        let length = Symbol::int(&text.len().to_string());
+        writeln!(out, "\t.word\t-1")?;
+        writeln!(out, "str_const{}:", string.index())?;
+        writeln!(out, "\t.word\t{}", STRING_TAG)?;
+        writeln!(out, "\t.word\t{}", HEADER_WORDS + 1 + (text.len() + 4) / 4)?;
+        writeln!(out, "\t.word\tString_dispTab")?;
+        writeln!(out, "\t.word\tint_const{}", length.index())?;
+        emit_ascii(out, text)?;
+        writeln!(out, "\t.byte\t0")?;
+        writeln!(out, "\t.align\t2")?;
+    }
+    for int in Table::Int.entries() {
+        // The digits are kept as written, but SPIM would read 007 as octal.
+        let digits = int.as_str().trim_start_matches('0');
+        let value = if digits.is_empty() { "0" } else { digits };
+        emit_word_object(out, &format!("int_const{}", int.index()), INT_TAG, "Int_dispTab", value)?;
+    }
+    emit_word_object(out, "bool_const0", BOOL_TAG, "Bool_dispTab", "0")?;
+    emit_word_object(out, "bool_const1", BOOL_TAG, "Bool_dispTab", "1")
+}
+
+// An object with a single word after its header.
+fn emit_word_object(out: &mut dyn Write, label: &str, tag: u32, dispatch: &str, value: &str) -> io::Result<()> {
+    writeln!(out, "\t.word\t-1")?;
+    writeln!(out, "{}:", label)?;
+    writeln!(out, "\t.word\t{}", tag)?;
+    writeln!(out, "\t.word\t{}", HEADER_WORDS + 1)?;
+    writeln!(out, "\t.word\t{}", dispatch)?;
+    writeln!(out, "\t.word\t{}", value)
+}
+
+// Writes the bytes of a string, escaping what SPIM would misread and
+// giving characters it can't take in a .ascii their own .byte.
+fn emit_ascii(out: &mut dyn Write, text: &str) -> io::Result<()> {
+    let mut run = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'\n' => run.push_str("\\n"),
+            b'\t' => run.push_str("\\t"),
+            b'\\' => run.push_str("\\\\"),
+            b'"' => run.push_str("\\\""),
+            b' ' ..= b'~' => run.push(byte as char),
+            _ => {
+                if !run.is_empty() {
+                    writeln!(out, "\t.ascii\t\"{}\"", run)?;
+                    run.clear();
+                }
+                writeln!(out, "\t.byte\t{}", byte)?;
+            }
+        }
+    }
+    if !run.is_empty() {
+        writeln!(out, "\t.ascii\t\"{}\"", run)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constants() -> String {
+        let mut out = Vec::new();
+        emit_constants(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn emits_a_string_object_for_each_string_constant() {
+        let string = Symbol::string("a \"quoted\"\nline\x01");
+        let output = constants();
+        let length = Symbol::int("16");
+        let expected = [
+            "\t.word\t-1".to_string(),
+            format!("str_const{}:", string.index()),
+            "\t.word\t4".to_string(),
+            "\t.word\t9".to_string(),
+            "\t.word\tString_dispTab".to_string(),
+            format!("\t.word\tint_const{}", length.index()),
+            "\t.ascii\t\"a \\\"quoted\\\"\\nline\"".to_string(),
+            "\t.byte\t1".to_string(),
+            "\t.byte\t0".to_string(),
+            "\t.align\t2\n".to_string()
+        ].join("\n");
+        assert!(output.contains(&expected), "{}", output);
+    }
+
+    #[test]
+    fn emits_an_int_object_for_each_int_constant_and_the_bools() {
+        let int = Symbol::int("0042");
+        let output = constants();
+        let expected = format!("\t.word\t-1\nint_const{}:\n\t.word\t2\n\t.word\t4\n\t.word\tInt_dispTab\n\t.word\t42\n", int.index());
+        assert!(output.contains(&expected), "{}", output);
+        assert!(output.contains("bool_const1:\n\t.word\t3\n\t.word\t4\n\t.word\tBool_dispTab\n\t.word\t1\n"));
+        // The length of the empty string is always there.
+        assert!(output.contains(&format!("\t.word\tint_const{}\n\t.byte\t0\n", Symbol::int("0").index())));
+    }
+}
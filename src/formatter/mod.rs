@@ -0,0 +1,892 @@
+/*
+ * Turns a program back into Cool source, laid out consistently. Parsing
+ * the output gives back the same tree, apart from spans.
+ *
+ * Comments are kept between the same two tokens they were between. The
+ * only exceptions are parentheses the layout adds or drops and the lets
+ * it merges. A `--` comment in the middle of an expression ends the line,
+ * and the rest of the expression carries on below it. If a comment can't
+ * be kept in place, the program isn't formatted at all.
+ */
+
+use std::collections::VecDeque;
+
+use ast;
+use lexer;
+use lexer::{Comment, Token, TokenKind};
+use source::Span;
+use symbol;
+
+const INDENT: &str = "    ";
+
+// How tightly each kind of expression binds, from the Cool manual's
+// precedence table. An expression printed where something tighter is
+// needed gets parentheses.
+const LET: u8 = 0;
+const ASSIGN: u8 = 1;
+const NOT: u8 = 2;
+const COMPARISON: u8 = 3;
+const SUM: u8 = 4;
+const PRODUCT: u8 = 5;
+const ISVOID: u8 = 6;
+const NEGATION: u8 = 7;
+const DISPATCH: u8 = 8;
+const ATOM: u8 = 9;
+
+fn precedence(expr: &ast::Expression) -> u8 {
+    match expr.kind {
+        ast::ExpressionKind::Let(..) => LET,
+        ast::ExpressionKind::Assign(..) => ASSIGN,
+        ast::ExpressionKind::Not(_) => NOT,
+        ast::ExpressionKind::BinaryOperation(ref op, _, _) => match *op {
+            ast::BinOp::LessThan | ast::BinOp::LessThanEqual | ast::BinOp::Equal => COMPARISON,
+            ast::BinOp::Plus | ast::BinOp::Minus => SUM,
+            ast::BinOp::Mult | ast::BinOp::Divide => PRODUCT
+        },
+        ast::ExpressionKind::IsVoid(_) => ISVOID,
+        ast::ExpressionKind::Negation(_) => NEGATION,
+        ast::ExpressionKind::Dispatch(..) | ast::ExpressionKind::StaticDispatch(..) => DISPATCH,
+        _ => ATOM
+    }
+}
+
+fn binary_operator(op: &ast::BinOp) -> &'static str {
+    match *op {
+        ast::BinOp::Plus => "+",
+        ast::BinOp::Minus => "-",
+        ast::BinOp::Mult => "*",
+        ast::BinOp::Divide => "/",
+        ast::BinOp::LessThan => "<",
+        ast::BinOp::LessThanEqual => "<=",
+        ast::BinOp::Equal => "="
+    }
+}
+
+// Whether an expression fits on one line: blocks and cases never do, and
+// neither does anything containing one, or an if, while or let that has
+// another of those inside it.
+fn is_simple(expr: &ast::Expression) -> bool {
+    match expr.kind {
+        ast::ExpressionKind::Block(_) | ast::ExpressionKind::Case(..) => false,
+        ast::ExpressionKind::If(ref condition, ref then_branch, ref else_branch) => {
+            is_flat(condition) && is_flat(then_branch) && is_flat(else_branch)
+        },
+        ast::ExpressionKind::While(ref condition, ref body) => is_flat(condition) && is_flat(body),
+        // The bindings of nested lets go on one line, as one let.
+        ast::ExpressionKind::Let(_, _, ref init, ref body) => {
+            let body_fits = match body.kind {
+                ast::ExpressionKind::Let(..) => is_simple(body),
+                _ => is_flat(body)
+            };
+            is_flat(init) && body_fits
+        },
+        _ => children(expr).iter().all(|child| is_simple(child))
+    }
+}
+
+// Whether an expression has no compound expressions in it at all.
+fn is_flat(expr: &ast::Expression) -> bool {
+    match expr.kind {
+        ast::ExpressionKind::Block(_) | ast::ExpressionKind::Case(..) |
+        ast::ExpressionKind::If(..) | ast::ExpressionKind::While(..) |
+        ast::ExpressionKind::Let(..) => false,
+        _ => children(expr).iter().all(|child| is_flat(child))
+    }
+}
+
+fn children(expr: &ast::Expression) -> Vec<&ast::Expression> {
+    match expr.kind {
+        ast::ExpressionKind::Assign(_, ref value) => vec![value],
+        ast::ExpressionKind::Dispatch(ref object, _, ref args) |
+        ast::ExpressionKind::StaticDispatch(ref object, _, _, ref args) => {
+            let mut children = vec![&**object];
+            children.extend(args.iter());
+            children
+        },
+        ast::ExpressionKind::If(ref condition, ref then_branch, ref else_branch) => {
+            vec![condition, then_branch, else_branch]
+        },
+        ast::ExpressionKind::While(ref condition, ref body) => vec![condition, body],
+        ast::ExpressionKind::Let(_, _, ref init, ref body) => vec![init, body],
+        ast::ExpressionKind::Case(ref value, ref branches) => {
+            let mut children = vec![&**value];
+            children.extend(branches.iter().map(|branch| &*branch.expr));
+            children
+        },
+        ast::ExpressionKind::Block(ref statements) => statements.iter().collect(),
+        ast::ExpressionKind::IsVoid(ref operand) |
+        ast::ExpressionKind::Negation(ref operand) |
+        ast::ExpressionKind::Not(ref operand) => vec![operand],
+        ast::ExpressionKind::BinaryOperation(_, ref left, ref right) => vec![left, right],
+        _ => Vec::new()
+    }
+}
+
+// Writes a string literal back out with the escapes the lexer reads.
+fn quote(string: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in string.chars() {
+        match ch {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\x08' => quoted.push_str("\\b"),
+            '\x0c' => quoted.push_str("\\f"),
+            _ => quoted.push(ch)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn starts_before(comment: &Comment, line: usize, col: usize) -> bool {
+    (comment.span.start_line, comment.span.start_col) < (line, col)
+}
+
+// Where a token starts, or somewhere past the end for no token.
+fn start(token: Option<&Token>) -> (usize, usize) {
+    token.map_or((usize::MAX, 0), |token| (token.span.start_line, token.span.start_col))
+}
+
+fn feature_span(feature: &ast::Feature) -> Span {
+    match *feature {
+        ast::Feature::Method { span, .. } | ast::Feature::Attribute { span, .. } => span
+    }
+}
+
+struct Formatter<'a> {
+    out: String,
+    indent: usize,
+    // The comments not written out yet, in source order. Each is written
+    // before the next class, feature, statement or case branch that comes
+    // after it, or at the end of the line if it followed one of those.
+    // Any inside an expression are written next to the part of it they
+    // came before or after.
+    comments: VecDeque<&'a Comment>,
+    tokens: &'a [Token],
+    // Whether formatting keeps each of the tokens.
+    kept: Vec<bool>,
+    // Whether the line ends in a `--` comment, so that anything else has to
+    // go on the next one.
+    after_line_comment: bool
+}
+
+impl<'a> Formatter<'a> {
+    // Starts a new line at the current indentation.
+    fn newline(&mut self) {
+        self.after_line_comment = false;
+        self.out.push('\n');
+        for _ in 0 .. self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        let mut text = text;
+        if self.after_line_comment {
+            // The rest of the line was cut off, so it and the rest of what
+            // it's in carry on one level further in.
+            self.indent += 1;
+            self.newline();
+            text = text.trim_start_matches(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    fn inline_comment(&mut self, comment: &Comment) {
+        self.write(&comment.text);
+        self.after_line_comment = comment.text.starts_with("--");
+    }
+
+    // Writes the comments just before an expression in front of it, on the
+    // same line.
+    fn comments_in_front(&mut self, span: Span) {
+        while self.comments.front().is_some_and(|comment| starts_before(comment, span.start_line, span.start_col)) {
+            let comment = self.comments.pop_front().unwrap();
+            self.inline_comment(comment);
+            if !self.after_line_comment {
+                self.write(" ");
+            }
+        }
+    }
+
+    // Writes the comments between the end of an expression and the next
+    // token after it, other than a closing parenthesis that's dropped,
+    // after it. If that
+    // token is a semicolon, they're left to go after it instead.
+    fn comments_after(&mut self, span: Span) {
+        let next = self.next_token(span, |token, kept| kept || token.kind != TokenKind::RightParen);
+        if next.is_some_and(|token| token.kind == TokenKind::Semicolon) {
+            return;
+        }
+        let next = start(next);
+        while self.comments.front().is_some_and(|comment| starts_before(comment, next.0, next.1)) {
+            let comment = self.comments.pop_front().unwrap();
+            self.write(" ");
+            self.inline_comment(comment);
+        }
+    }
+
+    // Writes the comments that come before `span` on lines of their own,
+    // leaving a fresh line for whatever starts there.
+    fn comments_before(&mut self, span: Span) {
+        while self.comments.front().is_some_and(|comment| starts_before(comment, span.start_line, span.start_col)) {
+            let comment = self.comments.pop_front().unwrap();
+            self.write(&comment.text);
+            self.newline();
+        }
+    }
+
+    // Writes the comments left inside `span` on lines of their own, before
+    // whatever closes it.
+    fn comments_within(&mut self, span: Span) {
+        while self.comments.front().is_some_and(|comment| starts_before(comment, span.end_line, span.end_col)) {
+            let comment = self.comments.pop_front().unwrap();
+            self.newline();
+            self.write(&comment.text);
+        }
+    }
+
+    // The first token at or after the end of `span` that `wanted` accepts,
+    // given whether formatting keeps it.
+    fn next_token<F: Fn(&Token, bool) -> bool>(&self, span: Span, wanted: F) -> Option<&'a Token> {
+        let end = (span.end_line, span.end_col);
+        self.tokens.iter().zip(&self.kept)
+            .skip_while(|&(token, _)| (token.span.start_line, token.span.start_col) < end)
+            .find(|&(token, &kept)| wanted(token, kept))
+            .map(|(token, _)| token)
+    }
+
+    // Writes the comments right after something, up to the end of the line
+    // it ended on, after it on the same line. That includes any before the
+    // semicolon that ends it, which is written first.
+    fn trailing_comments(&mut self, span: Span) {
+        let next = start(self.next_token(span, |token, _| token.kind != TokenKind::Semicolon));
+        let end = match self.next_token(span, |_, _| true) {
+            Some(token) if token.kind == TokenKind::Semicolon => token.span,
+            _ => span
+        };
+        while self.comments.front().is_some_and(|comment| {
+            starts_before(comment, next.0, next.1) &&
+                (starts_before(comment, end.end_line, end.end_col) || comment.span.start_line == end.end_line)
+        }) {
+            let comment = self.comments.pop_front().unwrap();
+            self.write(" ");
+            self.write(&comment.text);
+        }
+    }
+
+    fn class(&mut self, class: &ast::Class) {
+        self.write(&format!("class {}", class.name));
+        if class.parent != symbol::OBJECT {
+            self.write(&format!(" inherits {}", class.parent));
+        }
+        self.write(" {");
+        let empty = self.out.len();
+        self.indent += 1;
+        let indent = self.indent;
+        for (i, feature) in class.features.iter().enumerate() {
+            // Methods are set apart from whatever is next to them.
+            let is_method = |feature: &ast::Feature| match *feature {
+                ast::Feature::Method { .. } => true,
+                ast::Feature::Attribute { .. } => false
+            };
+            if i > 0 && (is_method(feature) || is_method(&class.features[i - 1])) {
+                self.write("\n");
+            }
+            self.newline();
+            self.comments_before(feature_span(feature));
+            self.feature(feature);
+            self.indent = indent;
+            self.trailing_comments(feature_span(feature));
+        }
+        self.comments_within(class.span);
+        self.indent -= 1;
+        if self.out.len() == empty {
+            self.write("};");
+        } else {
+            self.newline();
+            self.write("};");
+        }
+    }
+
+    fn feature(&mut self, feature: &ast::Feature) {
+        match *feature {
+            ast::Feature::Method { ref name, ref params, ref return_type, ref body, .. } => {
+                let params: Vec<String> = params.iter()
+                    .map(|param| format!("{} : {}", param.name, param.cool_type))
+                    .collect();
+                self.write(&format!("{}({}) : {} {{", name, params.join(", "), return_type));
+                self.indent += 1;
+                self.newline();
+                self.comments_before(body.span);
+                self.expression(body, LET);
+                self.comments_within(feature_span(feature));
+                self.indent -= 1;
+                self.newline();
+                self.write("};");
+            },
+            ast::Feature::Attribute { ref name, ref cool_type, ref expr, .. } => {
+                self.write(&format!("{} : {}", name, cool_type));
+                self.init(expr);
+                self.write(";");
+            }
+        }
+    }
+
+    fn init(&mut self, expr: &ast::Expression) {
+        if let ast::ExpressionKind::NoExpr = expr.kind {
+            return;
+        }
+        self.write(" <- ");
+        self.expression(expr, ASSIGN);
+    }
+
+    // Writes an expression where one binding at least as tightly as
+    // `context` is needed, in parentheses if it doesn't.
+    fn expression(&mut self, expr: &ast::Expression, context: u8) {
+        let indent = self.indent;
+        if precedence(expr) < context {
+            self.write("(");
+            self.expression(expr, LET);
+            self.write(")");
+        } else {
+            self.comments_in_front(expr.span);
+            self.expression_kind(expr);
+            self.comments_after(expr.span);
+        }
+        self.indent = indent;
+    }
+
+    fn expression_kind(&mut self, expr: &ast::Expression) {
+        match expr.kind {
+            ast::ExpressionKind::Assign(ref name, ref value) => {
+                self.write(&format!("{} <- ", name));
+                self.expression(value, ASSIGN);
+            },
+            ast::ExpressionKind::Dispatch(ref object, ref method, ref args) => {
+                // A dispatch on an implicit self shares its span with the
+                // self, since it has no text of its own.
+                let implicit_self = match object.kind {
+                    ast::ExpressionKind::Identifier(name) => name == symbol::SELF && object.span == expr.span,
+                    _ => false
+                };
+                if !implicit_self {
+                    self.expression(object, DISPATCH);
+                    self.write(".");
+                }
+                self.call(method.as_str(), args, expr.span);
+            },
+            ast::ExpressionKind::StaticDispatch(ref object, ref static_type, ref method, ref args) => {
+                self.expression(object, DISPATCH);
+                self.write(&format!("@{}.", static_type));
+                self.call(method.as_str(), args, expr.span);
+            },
+            ast::ExpressionKind::If(ref condition, ref then_branch, ref else_branch) => {
+                if is_simple(expr) {
+                    self.write("if ");
+                    self.expression(condition, LET);
+                    self.write(" then ");
+                    self.expression(then_branch, LET);
+                    self.write(" else ");
+                    self.expression(else_branch, LET);
+                    self.write(" fi");
+                } else {
+                    self.write("if ");
+                    self.expression(condition, LET);
+                    self.write(" then");
+                    self.indented(then_branch);
+                    self.newline();
+                    self.write("else");
+                    self.indented(else_branch);
+                    self.newline();
+                    self.write("fi");
+                }
+            },
+            ast::ExpressionKind::While(ref condition, ref body) => {
+                self.write("while ");
+                self.expression(condition, LET);
+                if is_simple(expr) {
+                    self.write(" loop ");
+                    self.expression(body, LET);
+                    self.write(" pool");
+                } else {
+                    self.write(" loop");
+                    self.indented(body);
+                    self.newline();
+                    self.write("pool");
+                }
+            },
+            ast::ExpressionKind::Let(..) => self.let_expression(expr),
+            ast::ExpressionKind::Case(ref value, ref branches) => {
+                self.write("case ");
+                self.expression(value, LET);
+                self.write(" of");
+                self.indent += 1;
+                let indent = self.indent;
+                for branch in branches {
+                    self.newline();
+                    self.comments_before(branch.span);
+                    self.write(&format!("{} : {} => ", branch.name, branch.cool_type));
+                    self.expression(&branch.expr, LET);
+                    self.write(";");
+                    self.indent = indent;
+                    self.trailing_comments(branch.span);
+                }
+                self.comments_within(expr.span);
+                self.indent -= 1;
+                self.newline();
+                self.write("esac");
+            },
+            ast::ExpressionKind::Block(ref statements) => {
+                self.write("{");
+                self.indent += 1;
+                let indent = self.indent;
+                for statement in statements {
+                    self.newline();
+                    self.comments_before(statement.span);
+                    self.expression(statement, LET);
+                    self.write(";");
+                    self.indent = indent;
+                    self.trailing_comments(statement.span);
+                }
+                self.comments_within(expr.span);
+                self.indent -= 1;
+                self.newline();
+                self.write("}");
+            },
+            ast::ExpressionKind::New(ref cool_type) => self.write(&format!("new {}", cool_type)),
+            ast::ExpressionKind::IsVoid(ref operand) => {
+                self.write("isvoid ");
+                self.expression(operand, ISVOID);
+            },
+            ast::ExpressionKind::BinaryOperation(ref op, ref left, ref right) => {
+                // Arithmetic associates to the left, so a right operand at
+                // the same level needs parentheses; comparisons don't
+                // associate at all.
+                let level = precedence(expr);
+                let left_context = if level == COMPARISON { level + 1 } else { level };
+                self.expression(left, left_context);
+                self.write(&format!(" {} ", binary_operator(op)));
+                self.expression(right, level + 1);
+            },
+            ast::ExpressionKind::Negation(ref operand) => {
+                self.write("~");
+                self.expression(operand, NEGATION);
+            },
+            ast::ExpressionKind::Not(ref operand) => {
+                self.write("not ");
+                self.expression(operand, NOT);
+            },
+            ast::ExpressionKind::Identifier(name) => self.write(name.as_str()),
+            ast::ExpressionKind::IntLiteral(digits) => self.write(digits.as_str()),
+            ast::ExpressionKind::StringLiteral(string) => self.write(&quote(string.as_str())),
+            ast::ExpressionKind::True => self.write("true"),
+            ast::ExpressionKind::False => self.write("false"),
+            ast::ExpressionKind::NoExpr => {}
+        }
+    }
+
+    // Writes an expression on its own line, one level further in.
+    fn indented(&mut self, expr: &ast::Expression) {
+        self.indent += 1;
+        self.newline();
+        self.comments_before(expr.span);
+        self.expression(expr, LET);
+        self.indent -= 1;
+    }
+
+    fn call(&mut self, method: &str, args: &[ast::Expression], span: Span) {
+        self.write(&format!("{}(", method));
+        if args.is_empty() {
+            // Comments between the parentheses stay there.
+            while self.comments.front().is_some_and(|comment| starts_before(comment, span.end_line, span.end_col)) {
+                let comment = self.comments.pop_front().unwrap();
+                self.inline_comment(comment);
+            }
+        }
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.expression(arg, LET);
+        }
+        self.write(")");
+    }
+
+    // A let whose body is another let is written as one let with several
+    // bindings, which parses to the same thing.
+    fn let_expression(&mut self, expr: &ast::Expression) {
+        self.write("let ");
+        let mut body = expr;
+        let mut first = true;
+        while let ast::ExpressionKind::Let(ref name, ref cool_type, ref init, ref inner) = body.kind {
+            if !first {
+                self.write(", ");
+                // Whatever came before the name comes after the comma.
+                let start = Span { end_line: body.span.start_line, end_col: body.span.start_col, ..body.span };
+                let name = self.next_token(start, |token, _| matches!(token.kind, TokenKind::Identifier(_)));
+                self.comments_in_front(name.map_or(body.span, |name| name.span));
+            }
+            first = false;
+            self.write(&format!("{} : {}", name, cool_type));
+            self.init(init);
+            body = inner;
+        }
+        if is_simple(expr) {
+            self.write(" in ");
+            self.expression(body, LET);
+        } else {
+            self.write(" in");
+            self.indented(body);
+        }
+    }
+}
+
+pub struct FormatError {
+    pub span: Span,
+    pub message: String
+}
+
+// What an open parenthesis or let in a list of tokens is.
+enum Open {
+    Call,
+    Group,
+    Let
+}
+
+// Whether formatting keeps each token. Parentheses around an expression
+// may be added or taken away, and a let whose body is another let is
+// written as one, so `in let` turns into a comma. The rest are kept.
+fn kept_tokens(tokens: &[Token]) -> Vec<bool> {
+    let mut kept = Vec::new();
+    let mut open = Vec::new();
+    let mut after_name = false;
+    let mut merged_let = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let keep = match token.kind {
+            TokenKind::LeftParen => {
+                open.push(if after_name { Open::Call } else { Open::Group });
+                after_name
+            },
+            TokenKind::RightParen => matches!(open.pop(), Some(Open::Call)),
+            TokenKind::Let => {
+                open.push(Open::Let);
+                !merged_let
+            },
+            TokenKind::In => {
+                open.pop();
+                let next = tokens[i + 1 ..].iter().find(|token| token.kind != TokenKind::LeftParen);
+                !next.is_some_and(|token| token.kind == TokenKind::Let)
+            },
+            TokenKind::Comma => !matches!(open.last(), Some(Open::Let)),
+            _ => true
+        };
+        kept.push(keep);
+        if token.kind != TokenKind::LeftParen {
+            merged_let = token.kind == TokenKind::In && !keep;
+        }
+        after_name = matches!(token.kind, TokenKind::Identifier(_));
+    }
+    kept
+}
+
+// For each comment, in order, how many of the tokens formatting keeps
+// come before it. A comment just before a semicolon counts as coming
+// after it, since that's where it's written.
+fn comment_positions(tokens: &[Token]) -> Vec<usize> {
+    let mut positions = Vec::new();
+    let mut index = 0;
+    for (token, kept) in tokens.iter().zip(kept_tokens(tokens)) {
+        let position = if token.kind == TokenKind::Semicolon { index + 1 } else { index };
+        positions.extend(token.comments.iter().map(|_| position));
+        if kept {
+            index += 1;
+        }
+    }
+    positions
+}
+
+// Formats a whole program, with a blank line between classes. `tokens`
+// are the ones it was parsed from, which carry its comments if it was
+// lexed with lex_with_comments.
+pub fn format_program(program: &ast::Program, tokens: &[Token]) -> Result<String, FormatError> {
+    let formatted = layout(program, tokens);
+    // Check every comment ended up between the same tokens as before.
+    let file = tokens[0].span.file;
+    let formatted_tokens = match lexer::lex_with_comments(formatted.chars().peekable(), file) {
+        Ok(tokens) => tokens,
+        Err(_) => return Err(FormatError {
+            span: program.span,
+            message: "formatting this program gives something that doesn't lex, which is a bug in the formatter".to_string()
+        })
+    };
+    let comments: Vec<&Comment> = tokens.iter().flat_map(|token| token.comments.iter()).collect();
+    let before = comment_positions(tokens);
+    let after = comment_positions(&formatted_tokens);
+    if before.len() != after.len() {
+        return Err(FormatError {
+            span: comments.get(after.len()).map_or(program.span, |comment| comment.span),
+            message: format!("formatting this program gives {} comments instead of {}", after.len(), before.len())
+        });
+    }
+    match before.iter().zip(&after).position(|(before, after)| before != after) {
+        Some(moved) => Err(FormatError {
+            span: comments[moved].span,
+            message: "cannot format without moving this comment out of the expression it's in".to_string()
+        }),
+        None => Ok(formatted)
+    }
+}
+
+fn layout(program: &ast::Program, tokens: &[Token]) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        indent: 0,
+        comments: tokens.iter().flat_map(|token| token.comments.iter()).collect(),
+        tokens,
+        kept: kept_tokens(tokens),
+        after_line_comment: false
+    };
+    for (i, class) in program.classes.iter().enumerate() {
+        if i > 0 {
+            formatter.write("\n\n");
+        }
+        formatter.comments_before(class.span);
+        formatter.class(class);
+        formatter.trailing_comments(class.span);
+    }
+    while let Some(comment) = formatter.comments.pop_front() {
+        if !formatter.out.is_empty() {
+            formatter.newline();
+        }
+        formatter.write(&comment.text);
+    }
+    formatter.write("\n");
+    formatter.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+    use parser;
+    use source::FileId;
+    use std::fs;
+
+    fn parse(text: &str) -> (ast::Program, Vec<Token>) {
+        let tokens = lexer::lex_with_comments(text.chars().peekable(), FileId(0)).ok().unwrap();
+        match parser::parse_cool_program(&tokens, false) {
+            Ok(program) => (program, tokens),
+            Err(failure) => panic!("{}\n{}", failure.errors[0].message, text)
+        }
+    }
+
+    fn comments(tokens: &[Token]) -> Vec<&str> {
+        tokens.iter().flat_map(|token| token.comments.iter()).map(|comment| &comment.text[..]).collect()
+    }
+
+    // Formats `text`, checking that the result parses to the same tree,
+    // keeps every comment in order and is already formatted.
+    fn round_trip(text: &str) -> String {
+        let (program, tokens) = parse(text);
+        let formatted = format_program(&program, &tokens).ok().unwrap();
+        let (reparsed, reparsed_tokens) = parse(&formatted);
+        assert_eq!(ast::without_spans(&program), ast::without_spans(&reparsed), "{}", formatted);
+        assert_eq!(comments(&tokens), comments(&reparsed_tokens), "{}", formatted);
+        assert_eq!(format_program(&reparsed, &reparsed_tokens).ok().unwrap(), formatted);
+        formatted
+    }
+
+    #[test]
+    fn round_trips_the_example_programs() {
+        for entry in fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "cl") {
+                round_trip(&fs::read_to_string(&path).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_the_precedence_corpus() {
+        let corpus = fs::read_to_string("tests/golden/precedence.txt").unwrap();
+        for line in corpus.lines() {
+            let source = line.split("-->").next().unwrap().trim();
+            if !source.is_empty() && !source.starts_with('#') {
+                round_trip(&format!("class A {{ f() : Object {{ {} }}; }};", source));
+            }
+        }
+    }
+
+    #[test]
+    fn adds_only_the_parentheses_needed() {
+        let cases = [
+            ("(a + b) * c", "(a + b) * c"),
+            ("a + (b * c)", "a + b * c"),
+            ("(a - b) - c", "a - b - c"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("(a < b) = c", "(a < b) = c"),
+            ("not (a = b)", "not a = b"),
+            ("(not a) = b", "(not a) = b"),
+            ("~(a.f())", "~a.f()"),
+            ("(~a).f()", "(~a).f()"),
+            ("(a@A.f()).g()", "a@A.f().g()"),
+            ("x <- (y <- z)", "x <- y <- z"),
+            ("(let x : Int in x) + 1", "(let x : Int in x) + 1"),
+            ("self.f(1)", "self.f(1)"),
+            ("f(1)", "f(1)"),
+        ];
+        for &(source, expected) in &cases {
+            let formatted = round_trip(&format!("class A {{ f() : Object {{ {} }}; }};", source));
+            let body = formatted.lines().nth(2).unwrap().trim();
+            assert_eq!(body, expected, "formatting {}", source);
+        }
+    }
+
+    #[test]
+    fn lays_out_classes_and_expressions() {
+        let formatted = round_trip("
+            class A inherits IO { x:Int<-1; y:String; f(a:Int,b:Int):Int{ if a<b then a else b fi };
+            g():Object{{ while true loop x<-x+1 pool; let a:Int, b:Int<-2 in case a of i:Int=>i; o:Object=>0; esac; }}; };
+            class B{};
+        ");
+        assert_eq!(formatted, [
+            "class A inherits IO {",
+            "    x : Int <- 1;",
+            "    y : String;",
+            "",
+            "    f(a : Int, b : Int) : Int {",
+            "        if a < b then a else b fi",
+            "    };",
+            "",
+            "    g() : Object {",
+            "        {",
+            "            while true loop x <- x + 1 pool;",
+            "            let a : Int, b : Int <- 2 in",
+            "                case a of",
+            "                    i : Int => i;",
+            "                    o : Object => 0;",
+            "                esac;",
+            "        }",
+            "    };",
+            "};",
+            "",
+            "class B {};",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn escapes_strings() {
+        let formatted = round_trip(r#"class A { s : String <- "tab\there \"quoted\" back\\slash\nnew\bline\f"; };"#);
+        assert!(formatted.contains(r#""tab\there \"quoted\" back\\slash\nnew\bline\f""#), "{}", formatted);
+    }
+
+    #[test]
+    fn keeps_comments_in_place() {
+        let formatted = round_trip("
+            -- The only class.
+            class A { x:Int; -- a count
+            (* Does nothing. *)
+            f():Object{{ x; -- first
+            -- second
+            x;
+            -- last
+            }};
+            -- the end of A
+            };
+            -- trailing
+        ");
+        assert_eq!(formatted, [
+            "-- The only class.",
+            "class A {",
+            "    x : Int; -- a count",
+            "",
+            "    (* Does nothing. *)",
+            "    f() : Object {",
+            "        {",
+            "            x; -- first",
+            "            -- second",
+            "            x;",
+            "            -- last",
+            "        }",
+            "    };",
+            "    -- the end of A",
+            "};",
+            "-- trailing",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn keeps_comments_inside_expressions_where_they_were() {
+        let formatted = round_trip("class A { f(x : Bool) : Int { {
+            if x -- cond
+            then 1 else 2 fi;
+            x <- (* one *) (3);
+            g(1, -- first
+              2);
+            h( -- nothing
+            );
+            let a : Int <- 1 in -- then
+              let b : Int in b;
+        } }; x : Int <- -- the count
+            { 0; }; y : Int <- 1 -- one
+            ; };");
+        assert_eq!(formatted, [
+            "class A {",
+            "    f(x : Bool) : Int {",
+            "        {",
+            "            if x -- cond",
+            "                then 1 else 2 fi;",
+            "            x <- (* one *) 3;",
+            "            g(1, -- first",
+            "                2);",
+            "            h(-- nothing",
+            "                );",
+            "            let a : Int <- 1, -- then",
+            "                b : Int in b;",
+            "        }",
+            "    };",
+            "",
+            "    x : Int <- -- the count",
+            "        {",
+            "            0;",
+            "        };",
+            "    y : Int <- 1; -- one",
+            "};",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn counts_the_tokens_formatting_keeps_before_each_comment() {
+        let positions = |text: &str| {
+            let tokens = lexer::lex_with_comments(text.chars().peekable(), FileId(0)).ok().unwrap();
+            comment_positions(&tokens)
+        };
+        // A call's parentheses stay, but ones around an expression may not.
+        assert_eq!(positions("f((x) -- c\n)"), positions("f(x -- c\n)"));
+        assert_ne!(positions("f( -- c\n)"), positions("f() -- c\n"));
+        // Nor do the tokens between lets that are merged.
+        assert_eq!(positions("let a : Int in -- c\n let b : Int in b"), positions("let a : Int, -- c\n b : Int in b"));
+        assert_ne!(positions("let a : Int -- c\n in a"), positions("let a : Int in -- c\n a"));
+        // Nor does it matter which side of a semicolon a comment is on.
+        assert_eq!(positions("a -- c\n; b"), positions("a; -- c\n b"));
+    }
+
+    #[test]
+    fn refuses_to_move_comments_it_cant_keep_in_place() {
+        let cases = [
+            ("class A { f() : Object { new -- c\n Foo }; };", (1, 30)),
+            ("class A -- c\n inherits B {};", (1, 9)),
+        ];
+        for &(text, start) in &cases {
+            let (program, tokens) = parse(text);
+            let error = format_program(&program, &tokens).err().unwrap();
+            assert_eq!((error.span.start_line, error.span.start_col), start, "formatting {}", text);
+        }
+    }
+}
@@ -0,0 +1,802 @@
+/*
+ * A small JSON reader and writer, and the JSON form of the program tree,
+ * for tools written in other languages.
+ *
+ * Every node is an object with a "span" and, for features and
+ * expressions, a "kind" saying which sort of node it is:
+ *
+ *   program     { files: [string], classes: [class], span }
+ *   class       { name, parent, features: [feature], span }
+ *   feature     { kind: "method", name, params: [formal], return_type, body, span }
+ *               { kind: "attribute", name, type, init, span }
+ *   formal      { name, type, span }
+ *   expression  { kind: "assign", name, value, span }
+ *               { kind: "dispatch", object, method, args: [expression], span }
+ *               { kind: "static_dispatch", object, type, method, args, span }
+ *               { kind: "if", condition, then, else, span }
+ *               { kind: "while", condition, body, span }
+ *               { kind: "let", name, type, init, body, span }
+ *               { kind: "case", value, branches: [branch], span }
+ *               { kind: "block", statements: [expression], span }
+ *               { kind: "new", type, span }
+ *               { kind: "isvoid" | "negate" | "not", operand, span }
+ *               { kind: "binary", op: "+" | "-" | "*" | "/" | "<" | "<=" | "=",
+ *                 left, right, span }
+ *               { kind: "identifier", name, span }
+ *               { kind: "int", value: digits, span }
+ *               { kind: "string", value, span }
+ *               { kind: "bool", value: true | false, span }
+ *               { kind: "no_expr", span }
+ *   branch      { name, type, body, span }
+ *   span        { file, start_line, start_col, end_line, end_col }
+ *
+ * A span's file is an index into the program's files, which are named the
+ * way the compiler was given them. An int's value is a string of the
+ * digits it was written with, so that 007 comes back as 007.
+ */
+
+use std::char;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use ast;
+use source::{FileId, SourceMap, Span};
+use symbol::Symbol;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // Fields are kept in the order they were written.
+    Object(Vec<(String, Value)>)
+}
+
+impl Value {
+    fn get(&self, field: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref fields) => {
+                fields.iter().find(|entry| entry.0 == field).map(|entry| &entry.1)
+            },
+            _ => None
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in string.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\x08' => write!(f, "\\b")?,
+            '\x0c' => write!(f, "\\f")?,
+            '\0' ..= '\x1f' => write!(f, "\\u{:04x}", ch as u32)?,
+            _ => write!(f, "{}", ch)?
+        }
+    }
+    write!(f, "\"")
+}
+
+// Writes the value compactly, with no whitespace between tokens.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(ref string) => write_string(f, string),
+            Value::Array(ref values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            },
+            Value::Object(ref fields) => {
+                write!(f, "{{")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, &field.0)?;
+                    write!(f, ":{}", field.1)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    // Where the next character is, for error messages.
+    line: usize,
+    col: usize
+}
+
+impl<'a> Reader<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("{}:{}: {}", self.line, self.col, message))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.chars.next();
+        if ch == Some('\n') {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        ch
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&(' ' | '\t' | '\n' | '\r')) = self.chars.peek() {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&expected) {
+            self.next();
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", expected))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek().cloned() {
+            Some('{') => {
+                self.next();
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&'}') {
+                    self.next();
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.peek() != Some(&'"') {
+                        return self.error("expected a field name");
+                    }
+                    let name = self.string()?;
+                    self.expect(':')?;
+                    let value = self.value()?;
+                    fields.push((name, value));
+                    self.skip_whitespace();
+                    match self.chars.peek() {
+                        Some(&',') => {},
+                        Some(&'}') => {
+                            self.next();
+                            return Ok(Value::Object(fields));
+                        },
+                        _ => return self.error("expected ',' or '}'")
+                    }
+                    self.next();
+                }
+            },
+            Some('[') => {
+                self.next();
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&']') {
+                    self.next();
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.chars.peek() {
+                        Some(&',') => {},
+                        Some(&']') => {
+                            self.next();
+                            return Ok(Value::Array(values));
+                        },
+                        _ => return self.error("expected ',' or ']'")
+                    }
+                    self.next();
+                }
+            },
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('-' | '0' ..= '9') => self.number(),
+            Some('a' ..= 'z') => {
+                let mut word = String::new();
+                while let Some(&ch @ 'a' ..= 'z') = self.chars.peek() {
+                    word.push(ch);
+                    self.next();
+                }
+                match word.as_ref() {
+                    "null" => Ok(Value::Null),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => self.error(&format!("unexpected '{}'", word))
+                }
+            },
+            Some(ch) => self.error(&format!("unexpected '{}'", ch)),
+            None => self.error("unexpected end of input")
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let mut text = String::new();
+        while let Some(&ch @ ('-' | '+' | '.' | 'e' | 'E' | '0' ..= '9')) = self.chars.peek() {
+            text.push(ch);
+            self.next();
+        }
+        match text.parse() {
+            Ok(value) => Ok(Value::Number(value)),
+            Err(_) => self.error(&format!("bad number '{}'", text))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.next();
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let ch = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\x08',
+                        Some('f') => '\x0c',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return self.error("bad escape in string")
+                    };
+                    string.push(ch);
+                },
+                Some(ch) if ch < ' ' => return self.error("control character in string"),
+                Some(ch) => string.push(ch),
+                None => return self.error("unterminated string")
+            }
+        }
+    }
+
+    // The character after a \u, which may be the first half of a
+    // surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let first = self.hex_digits()?;
+        let code = if (0xd800 .. 0xdc00).contains(&first) {
+            if self.chars.peek() != Some(&'\\') {
+                return self.error("unpaired surrogate in string");
+            }
+            self.next();
+            if self.next() != Some('u') {
+                return self.error("unpaired surrogate in string");
+            }
+            let second = self.hex_digits()?;
+            if !(0xdc00 .. 0xe000).contains(&second) {
+                return self.error("unpaired surrogate in string");
+            }
+            0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+        } else {
+            first
+        };
+        match char::from_u32(code) {
+            Some(ch) => Ok(ch),
+            None => self.error("unpaired surrogate in string")
+        }
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0 .. 4 {
+            match self.next().and_then(|ch| ch.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return self.error("expected four hex digits")
+            }
+        }
+        Ok(code)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut reader = Reader { chars: text.chars().peekable(), line: 1, col: 1 };
+    let value = reader.value()?;
+    reader.skip_whitespace();
+    if reader.chars.peek().is_some() {
+        return reader.error("unexpected text after the value");
+    }
+    Ok(value)
+}
+
+fn object(kind: Option<&str>, fields: Vec<(&str, Value)>, span: Span) -> Value {
+    let mut object = Vec::new();
+    if let Some(kind) = kind {
+        object.push(("kind".to_string(), Value::String(kind.to_string())));
+    }
+    object.extend(fields.into_iter().map(|(name, value)| (name.to_string(), value)));
+    object.push(("span".to_string(), span_to_json(span)));
+    Value::Object(object)
+}
+
+fn string(string: &str) -> Value {
+    Value::String(string.to_string())
+}
+
+fn symbol(symbol: Symbol) -> Value {
+    string(symbol.as_str())
+}
+
+fn span_to_json(span: Span) -> Value {
+    let number = |n: usize| Value::Number(n as f64);
+    Value::Object(vec![
+        ("file".to_string(), number(span.file.0)),
+        ("start_line".to_string(), number(span.start_line)),
+        ("start_col".to_string(), number(span.start_col)),
+        ("end_line".to_string(), number(span.end_line)),
+        ("end_col".to_string(), number(span.end_col))
+    ])
+}
+
+// Writes a program, naming the files its spans are in. Everything in a
+// class is in the class's file.
+pub fn program_to_json(program: &ast::Program, sources: &SourceMap) -> Value {
+    let last = program.classes.iter().map(|class| class.span.file.0).chain(Some(program.span.file.0)).max().unwrap();
+    let files = (0 ..= last).map(|file| string(&sources.file(FileId(file)).name)).collect();
+    let classes = program.classes.iter().map(class_to_json).collect();
+    object(None, vec![("files", Value::Array(files)), ("classes", Value::Array(classes))], program.span)
+}
+
+fn class_to_json(class: &ast::Class) -> Value {
+    let features = class.features.iter().map(feature_to_json).collect();
+    object(None, vec![
+        ("name", symbol(class.name)),
+        ("parent", symbol(class.parent)),
+        ("features", Value::Array(features))
+    ], class.span)
+}
+
+fn feature_to_json(feature: &ast::Feature) -> Value {
+    match *feature {
+        ast::Feature::Method { ref name, ref params, ref return_type, ref body, span } => {
+            let params = params.iter().map(|param| {
+                object(None, vec![("name", symbol(param.name)), ("type", symbol(param.cool_type.name()))], param.span)
+            }).collect();
+            object(Some("method"), vec![
+                ("name", symbol(*name)),
+                ("params", Value::Array(params)),
+                ("return_type", symbol(return_type.name())),
+                ("body", expression_to_json(body))
+            ], span)
+        },
+        ast::Feature::Attribute { ref name, ref cool_type, ref expr, span } => {
+            object(Some("attribute"), vec![
+                ("name", symbol(*name)),
+                ("type", symbol(cool_type.name())),
+                ("init", expression_to_json(expr))
+            ], span)
+        }
+    }
+}
+
+fn binary_operator(op: &ast::BinOp) -> &'static str {
+    match *op {
+        ast::BinOp::Plus => "+",
+        ast::BinOp::Minus => "-",
+        ast::BinOp::Mult => "*",
+        ast::BinOp::Divide => "/",
+        ast::BinOp::LessThan => "<",
+        ast::BinOp::LessThanEqual => "<=",
+        ast::BinOp::Equal => "="
+    }
+}
+
+fn expression_to_json(expr: &ast::Expression) -> Value {
+    let list = |exprs: &[ast::Expression]| Value::Array(exprs.iter().map(expression_to_json).collect());
+    let (kind, fields) = match expr.kind {
+        ast::ExpressionKind::Assign(ref name, ref value) => {
+            ("assign", vec![("name", symbol(*name)), ("value", expression_to_json(value))])
+        },
+        ast::ExpressionKind::Dispatch(ref object, ref method, ref args) => {
+            ("dispatch", vec![
+                ("object", expression_to_json(object)),
+                ("method", symbol(*method)),
+                ("args", list(args))
+            ])
+        },
+        ast::ExpressionKind::StaticDispatch(ref object, ref static_type, ref method, ref args) => {
+            ("static_dispatch", vec![
+                ("object", expression_to_json(object)),
+                ("type", symbol(static_type.name())),
+                ("method", symbol(*method)),
+                ("args", list(args))
+            ])
+        },
+        ast::ExpressionKind::If(ref condition, ref then_branch, ref else_branch) => {
+            ("if", vec![
+                ("condition", expression_to_json(condition)),
+                ("then", expression_to_json(then_branch)),
+                ("else", expression_to_json(else_branch))
+            ])
+        },
+        ast::ExpressionKind::While(ref condition, ref body) => {
+            ("while", vec![("condition", expression_to_json(condition)), ("body", expression_to_json(body))])
+        },
+        ast::ExpressionKind::Let(ref name, ref cool_type, ref init, ref body) => {
+            ("let", vec![
+                ("name", symbol(*name)),
+                ("type", symbol(cool_type.name())),
+                ("init", expression_to_json(init)),
+                ("body", expression_to_json(body))
+            ])
+        },
+        ast::ExpressionKind::Case(ref value, ref branches) => {
+            let branches = branches.iter().map(|branch| {
+                object(None, vec![
+                    ("name", symbol(branch.name)),
+                    ("type", symbol(branch.cool_type.name())),
+                    ("body", expression_to_json(&branch.expr))
+                ], branch.span)
+            }).collect();
+            ("case", vec![("value", expression_to_json(value)), ("branches", Value::Array(branches))])
+        },
+        ast::ExpressionKind::Block(ref statements) => ("block", vec![("statements", list(statements))]),
+        ast::ExpressionKind::New(ref cool_type) => ("new", vec![("type", symbol(cool_type.name()))]),
+        ast::ExpressionKind::IsVoid(ref operand) => ("isvoid", vec![("operand", expression_to_json(operand))]),
+        ast::ExpressionKind::BinaryOperation(ref op, ref left, ref right) => {
+            ("binary", vec![
+                ("op", string(binary_operator(op))),
+                ("left", expression_to_json(left)),
+                ("right", expression_to_json(right))
+            ])
+        },
+        ast::ExpressionKind::Negation(ref operand) => ("negate", vec![("operand", expression_to_json(operand))]),
+        ast::ExpressionKind::Not(ref operand) => ("not", vec![("operand", expression_to_json(operand))]),
+        ast::ExpressionKind::Identifier(ref name) => ("identifier", vec![("name", symbol(*name))]),
+        ast::ExpressionKind::IntLiteral(digits) => ("int", vec![("value", symbol(digits))]),
+        ast::ExpressionKind::StringLiteral(value) => ("string", vec![("value", symbol(value))]),
+        ast::ExpressionKind::True => ("bool", vec![("value", Value::Bool(true))]),
+        ast::ExpressionKind::False => ("bool", vec![("value", Value::Bool(false))]),
+        ast::ExpressionKind::NoExpr => ("no_expr", Vec::new())
+    };
+    object(Some(kind), fields, expr.span)
+}
+
+// Reading the tree back. Errors name the field that was missing or had
+// the wrong sort of value.
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    match value.get(name) {
+        Some(field) => Ok(field),
+        None => Err(format!("missing field '{}' in {}", name, value))
+    }
+}
+
+fn string_field(value: &Value, name: &str) -> Result<String, String> {
+    match *field(value, name)? {
+        Value::String(ref string) => Ok(string.clone()),
+        ref other => Err(format!("field '{}' should be a string, not {}", name, other))
+    }
+}
+
+fn symbol_field(value: &Value, name: &str) -> Result<ast::Symbol, String> {
+    string_field(value, name).map(|text| Symbol::id(&text))
+}
+
+fn type_field(value: &Value, name: &str) -> Result<ast::CoolType, String> {
+    symbol_field(value, name).map(ast::CoolType::new)
+}
+
+fn array_field<'a>(value: &'a Value, name: &str) -> Result<&'a [Value], String> {
+    match *field(value, name)? {
+        Value::Array(ref values) => Ok(values),
+        ref other => Err(format!("field '{}' should be an array, not {}", name, other))
+    }
+}
+
+// A number that has to be a whole number in range for an i64.
+fn integer_field(value: &Value, name: &str) -> Result<i64, String> {
+    match *field(value, name)? {
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 9.0e15 => Ok(n as i64),
+        ref other => Err(format!("field '{}' should be an integer, not {}", name, other))
+    }
+}
+
+fn position_field(value: &Value, name: &str) -> Result<usize, String> {
+    match integer_field(value, name)? {
+        n if n >= 0 => Ok(n as usize),
+        n => Err(format!("field '{}' should not be negative, not {}", name, n))
+    }
+}
+
+// A span's file is an index into the files the tree was written with.
+fn span_from_json(value: &Value, files: &[FileId]) -> Result<Span, String> {
+    let span = field(value, "span")?;
+    let file = match files.get(position_field(span, "file")?) {
+        Some(&file) => file,
+        None => return Err(format!("span {} names a file that isn't in 'files'", span))
+    };
+    Ok(Span::new(file,
+                 (position_field(span, "start_line")?, position_field(span, "start_col")?),
+                 (position_field(span, "end_line")?, position_field(span, "end_col")?)))
+}
+
+fn expression_field(value: &Value, name: &str, files: &[FileId]) -> Result<Box<ast::Expression>, String> {
+    expression_from_json(field(value, name)?, files).map(Box::new)
+}
+
+fn expressions_field(value: &Value, name: &str, files: &[FileId]) -> Result<Vec<ast::Expression>, String> {
+    array_field(value, name)?.iter().map(|expr| expression_from_json(expr, files)).collect()
+}
+
+// Reads a tree written by program_to_json. The files it names are added
+// to `sources` if need be.
+pub fn program_from_json(value: &Value, sources: &mut SourceMap) -> Result<ast::Program, String> {
+    let files = array_field(value, "files")?.iter().map(|name| match *name {
+        Value::String(ref name) => Ok(sources.named(name)),
+        ref other => Err(format!("file names should be strings, not {}", other))
+    }).collect::<Result<Vec<_>, _>>()?;
+    let files = &files[..];
+    Ok(ast::Program {
+        classes: array_field(value, "classes")?.iter().map(|class| class_from_json(class, files)).collect::<Result<_, _>>()?,
+        span: span_from_json(value, files)?
+    })
+}
+
+fn class_from_json(value: &Value, files: &[FileId]) -> Result<ast::Class, String> {
+    Ok(ast::Class {
+        name: symbol_field(value, "name")?,
+        parent: symbol_field(value, "parent")?,
+        features: array_field(value, "features")?.iter().map(|feature| feature_from_json(feature, files)).collect::<Result<_, _>>()?,
+        span: span_from_json(value, files)?
+    })
+}
+
+fn feature_from_json(value: &Value, files: &[FileId]) -> Result<ast::Feature, String> {
+    let span = span_from_json(value, files)?;
+    match string_field(value, "kind")?.as_ref() {
+        "method" => {
+            let params = array_field(value, "params")?.iter().map(|param| {
+                Ok(ast::Formal {
+                    name: symbol_field(param, "name")?,
+                    cool_type: type_field(param, "type")?,
+                    span: span_from_json(param, files)?
+                })
+            }).collect::<Result<_, String>>()?;
+            Ok(ast::Feature::Method {
+                name: symbol_field(value, "name")?,
+                params,
+                return_type: type_field(value, "return_type")?,
+                body: expression_field(value, "body", files)?,
+                span
+            })
+        },
+        "attribute" => Ok(ast::Feature::Attribute {
+            name: symbol_field(value, "name")?,
+            cool_type: type_field(value, "type")?,
+            expr: expression_field(value, "init", files)?,
+            span
+        }),
+        kind => Err(format!("unknown kind of feature '{}'", kind))
+    }
+}
+
+fn expression_from_json(value: &Value, files: &[FileId]) -> Result<ast::Expression, String> {
+    let kind = match string_field(value, "kind")?.as_ref() {
+        "assign" => ast::ExpressionKind::Assign(symbol_field(value, "name")?, expression_field(value, "value", files)?),
+        "dispatch" => ast::ExpressionKind::Dispatch(
+            expression_field(value, "object", files)?,
+            symbol_field(value, "method")?,
+            expressions_field(value, "args", files)?),
+        "static_dispatch" => ast::ExpressionKind::StaticDispatch(
+            expression_field(value, "object", files)?,
+            type_field(value, "type")?,
+            symbol_field(value, "method")?,
+            expressions_field(value, "args", files)?),
+        "if" => ast::ExpressionKind::If(
+            expression_field(value, "condition", files)?,
+            expression_field(value, "then", files)?,
+            expression_field(value, "else", files)?),
+        "while" => ast::ExpressionKind::While(expression_field(value, "condition", files)?, expression_field(value, "body", files)?),
+        "let" => ast::ExpressionKind::Let(
+            symbol_field(value, "name")?,
+            type_field(value, "type")?,
+            expression_field(value, "init", files)?,
+            expression_field(value, "body", files)?),
+        "case" => {
+            let branches = array_field(value, "branches")?.iter().map(|branch| {
+                Ok(ast::CaseBranch {
+                    name: symbol_field(branch, "name")?,
+                    cool_type: type_field(branch, "type")?,
+                    expr: expression_field(branch, "body", files)?,
+                    span: span_from_json(branch, files)?
+                })
+            }).collect::<Result<_, String>>()?;
+            ast::ExpressionKind::Case(expression_field(value, "value", files)?, branches)
+        },
+        "block" => ast::ExpressionKind::Block(expressions_field(value, "statements", files)?),
+        "new" => ast::ExpressionKind::New(type_field(value, "type")?),
+        "isvoid" => ast::ExpressionKind::IsVoid(expression_field(value, "operand", files)?),
+        "binary" => {
+            let op = match string_field(value, "op")?.as_ref() {
+                "+" => ast::BinOp::Plus,
+                "-" => ast::BinOp::Minus,
+                "*" => ast::BinOp::Mult,
+                "/" => ast::BinOp::Divide,
+                "<" => ast::BinOp::LessThan,
+                "<=" => ast::BinOp::LessThanEqual,
+                "=" => ast::BinOp::Equal,
+                op => return Err(format!("unknown operator '{}'", op))
+            };
+            ast::ExpressionKind::BinaryOperation(op, expression_field(value, "left", files)?, expression_field(value, "right", files)?)
+        },
+        "negate" => ast::ExpressionKind::Negation(expression_field(value, "operand", files)?),
+        "not" => ast::ExpressionKind::Not(expression_field(value, "operand", files)?),
+        "identifier" => ast::ExpressionKind::Identifier(symbol_field(value, "name")?),
+        "int" => {
+            // Kept as the digits it was written with, like the int table.
+            let digits = string_field(value, "value")?;
+            if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(format!("integer '{}' should be written with digits only", digits));
+            }
+            if digits.parse::<i32>().is_err() {
+                return Err(format!("integer {} is out of range", digits));
+            }
+            ast::ExpressionKind::IntLiteral(Symbol::int(&digits))
+        },
+        "string" => ast::ExpressionKind::StringLiteral(Symbol::string(&string_field(value, "value")?)),
+        "bool" => match *field(value, "value")? {
+            Value::Bool(true) => ast::ExpressionKind::True,
+            Value::Bool(false) => ast::ExpressionKind::False,
+            ref other => return Err(format!("field 'value' should be a boolean, not {}", other))
+        },
+        "no_expr" => ast::ExpressionKind::NoExpr,
+        kind => return Err(format!("unknown kind of expression '{}'", kind))
+    };
+    Ok(ast::Expression::new(kind, span_from_json(value, files)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use lexer;
+    use parser;
+
+    #[test]
+    fn reads_what_it_writes() {
+        let text = r#"{"a":[1,-2.5,1e3,true,false,null],"b":"quote \" slash \\ tab \t nul \u0000 é 😀","c":{}}"#;
+        let value = parse(text).unwrap();
+        assert_eq!(value.get("a"), Some(&Value::Array(vec![
+            Value::Number(1.0),
+            Value::Number(-2.5),
+            Value::Number(1000.0),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Null
+        ])));
+        assert_eq!(value.get("b"), Some(&Value::String("quote \" slash \\ tab \t nul \0 é 😀".to_string())));
+        assert_eq!(parse(&value.to_string()).unwrap(), value);
+        assert_eq!(parse(r#" [ "\ud83d\ude00" , {} ] "#).unwrap(),
+                   Value::Array(vec![Value::String("😀".to_string()), Value::Object(Vec::new())]));
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let cases = [
+            ("", "1:1: unexpected end of input"),
+            ("[1, 2", "1:6: expected ',' or ']'"),
+            ("{\"a\" 1}", "1:6: expected ':'"),
+            ("{\"a\": 1,}", "1:9: expected a field name"),
+            ("nope", "1:5: unexpected 'nope'"),
+            ("\"\\ud800\"", "1:8: unpaired surrogate in string"),
+            ("1 2", "1:3: unexpected text after the value"),
+        ];
+        for &(text, expected) in &cases {
+            assert_eq!(parse(text), Err(expected.to_string()), "parsing {}", text);
+        }
+    }
+
+    #[test]
+    fn round_trips_the_example_programs() {
+        for entry in fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let text = fs::read_to_string(&path).unwrap();
+            let mut sources = SourceMap::new();
+            let file = sources.add(path.display().to_string(), text.clone());
+            let tokens = lexer::lex(text.chars().peekable(), file).ok().unwrap();
+            let program = parser::parse_cool_program(&tokens, false).ok().unwrap();
+            let json = program_to_json(&program, &sources).to_string();
+            let read = program_from_json(&parse(&json).unwrap(), &mut sources).unwrap();
+            assert!(read == program, "{:?} changed after reading back", path);
+        }
+    }
+
+    #[test]
+    fn keeps_the_files_and_int_digits_it_was_written_with() {
+        let mut sources = SourceMap::new();
+        let mut classes = Vec::new();
+        for &(name, text) in &[("a.cl", "class A { x : Int <- 007; };"), ("b.cl", "class B {};")] {
+            let file = sources.add(name.to_string(), text.to_string());
+            let tokens = lexer::lex(text.chars().peekable(), file).ok().unwrap();
+            classes.extend(parser::parse_cool_program(&tokens, false).ok().unwrap().classes);
+        }
+        let program = ast::Program { span: classes[0].span.to(classes[1].span), classes };
+        let json = program_to_json(&program, &sources).to_string();
+
+        // Read into a compiler that was given other files first.
+        let mut other_sources = SourceMap::new();
+        other_sources.add("tree.json".to_string(), json.clone());
+        let read = program_from_json(&parse(&json).unwrap(), &mut other_sources).unwrap();
+        let names: Vec<&str> = read.classes.iter().map(|class| &other_sources.file(class.span.file).name[..]).collect();
+        assert_eq!(names, ["a.cl", "b.cl"]);
+        match read.classes[0].features[0] {
+            ast::Feature::Attribute { ref expr, .. } => match expr.kind {
+                ast::ExpressionKind::IntLiteral(digits) => assert_eq!(digits.as_str(), "007"),
+                ref other => panic!("read back {:?}", other)
+            },
+            ref other => panic!("read back {:?}", other)
+        }
+    }
+
+    #[test]
+    fn writes_a_stable_schema() {
+        let text = "class A { f(x : Int) : Int { x + 1 }; };";
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.cl".to_string(), text.to_string());
+        let tokens = lexer::lex(text.chars().peekable(), file).ok().unwrap();
+        let program = parser::parse_cool_program(&tokens, false).ok().unwrap();
+        let span = |start_col, end_col| {
+            format!(r#""span":{{"file":0,"start_line":1,"start_col":{},"end_line":1,"end_col":{}}}"#, start_col, end_col)
+        };
+        assert_eq!(program_to_json(&program, &sources).to_string(), format!(concat!(
+            r#"{{"files":["a.cl"],"classes":[{{"name":"A","parent":"Object","features":[{{"kind":"method","name":"f","#,
+            r#""params":[{{"name":"x","type":"Int",{}}}],"return_type":"Int","#,
+            r#""body":{{"kind":"binary","op":"+","left":{{"kind":"identifier","name":"x",{}}},"#,
+            r#""right":{{"kind":"int","value":"1",{}}},{}}},{}}}],{}}}],{}}}"#),
+            span(13, 20), span(30, 31), span(34, 35), span(30, 35), span(11, 38), span(1, 40), span(1, 41)));
+    }
+
+    #[test]
+    fn reports_what_is_wrong_with_a_tree() {
+        let span = r#""span":{"file":0,"start_line":1,"start_col":1,"end_line":1,"end_col":2}"#;
+        let cases = [
+            (format!(r#"{{"files":["a.cl"],"classes":[{{"name":"A","parent":"Object",{}}}],{}}}"#, span, span),
+             "missing field 'features' in"),
+            (format!(r#"{{"classes":[],{}}}"#, span),
+             "missing field 'files' in"),
+            (format!(r#"{{"kind":"no_expr",{}}}"#, span.replace(r#""file":0"#, r#""file":1"#)),
+             "span {\"file\":1,"),
+            (format!(r#"{{"kind":"binary","op":"%","left":null,"right":null,{}}}"#, span),
+             "unknown operator '%'"),
+            (format!(r#"{{"kind":"int","value":"2147483648",{}}}"#, span),
+             "integer 2147483648 is out of range"),
+            (format!(r#"{{"kind":"int","value":"-1",{}}}"#, span),
+             "integer '-1' should be written with digits only"),
+            (format!(r#"{{"kind":"int","value":1,{}}}"#, span),
+             "field 'value' should be a string, not 1"),
+            (format!(r#"{{"kind":"loop",{}}}"#, span),
+             "unknown kind of expression 'loop'"),
+        ];
+        for &(ref text, expected) in &cases {
+            let value = parse(text).unwrap();
+            let result = if value.get("classes").is_some() {
+                program_from_json(&value, &mut SourceMap::new()).map(|_| ())
+            } else {
+                expression_from_json(&value, &[FileId(0)]).map(|_| ())
+            };
+            let message = result.unwrap_err();
+            assert!(message.starts_with(expected), "{} gave {}", text, message);
+        }
+    }
+}
@@ -1,13 +1,17 @@
+use std::fmt;
 use std::iter::Peekable;
-use std::rc::Rc;
 use std::str::Chars;
 
-#[derive(Debug)]
-pub enum Token {
-    Type (Rc<String>),
-    Identifier (Rc<String>),
-    IntegerLiteral (i32),
-    StringLiteral (Rc<String>),
+use source::FileId;
+use source::Span;
+use symbol::Symbol;
+
+#[derive(Debug, PartialEq)]
+pub enum TokenKind {
+    Type (Symbol),
+    Identifier (Symbol),
+    IntegerLiteral (Symbol),
+    StringLiteral (Symbol),
     Case,
     Class,
     Else,
@@ -46,7 +50,121 @@ pub enum Token {
     LessThanEqual,
     Arrow,
     Assign,
-    EOF
+    // Stands in for the text that caused a lexical error, the same way
+    // the reference lexer emits an ERROR token and carries on.
+    Error (LexErrorKind),
+    Eof
+}
+
+// How a token is described to users in diagnostics.
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match *self {
+            TokenKind::Type(ref name) => return write!(f, "type '{}'", name),
+            TokenKind::Identifier(ref name) => return write!(f, "identifier '{}'", name),
+            TokenKind::IntegerLiteral(value) => return write!(f, "integer {}", value),
+            TokenKind::StringLiteral(_) => "a string",
+            TokenKind::Case => "'case'",
+            TokenKind::Class => "'class'",
+            TokenKind::Else => "'else'",
+            TokenKind::Esac => "'esac'",
+            TokenKind::False => "'false'",
+            TokenKind::Fi => "'fi'",
+            TokenKind::If => "'if'",
+            TokenKind::In => "'in'",
+            TokenKind::Inherits => "'inherits'",
+            TokenKind::Isvoid => "'isvoid'",
+            TokenKind::Let => "'let'",
+            TokenKind::Loop => "'loop'",
+            TokenKind::New => "'new'",
+            TokenKind::Not => "'not'",
+            TokenKind::Of => "'of'",
+            TokenKind::Pool => "'pool'",
+            TokenKind::Then => "'then'",
+            TokenKind::True => "'true'",
+            TokenKind::While => "'while'",
+            TokenKind::LeftBrace => "'{'",
+            TokenKind::RightBrace => "'}'",
+            TokenKind::LeftParen => "'('",
+            TokenKind::RightParen => "')'",
+            TokenKind::Colon => "':'",
+            TokenKind::Semicolon => "';'",
+            TokenKind::Dot => "'.'",
+            TokenKind::Comma => "','",
+            TokenKind::At => "'@'",
+            TokenKind::Plus => "'+'",
+            TokenKind::Minus => "'-'",
+            TokenKind::Times => "'*'",
+            TokenKind::Divide => "'/'",
+            TokenKind::Tilde => "'~'",
+            TokenKind::LessThan => "'<'",
+            TokenKind::Equal => "'='",
+            TokenKind::LessThanEqual => "'<='",
+            TokenKind::Arrow => "'=>'",
+            TokenKind::Assign => "'<-'",
+            TokenKind::Error(_) => "an invalid token",
+            TokenKind::Eof => "end of file"
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// Strings longer than this are rejected, as in the reference lexer.
+pub const MAX_STRING_LENGTH: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexErrorKind {
+    InvalidChar(char),
+    IntOverflow(String),
+    UnterminatedString,
+    EofInString,
+    NullInString,
+    EscapedNullInString,
+    StringTooLong,
+    EofInComment,
+    UnmatchedCommentEnd
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LexErrorKind::InvalidChar(ch) => write!(f, "Invalid character {:?}", ch),
+            LexErrorKind::IntOverflow(ref digits) => write!(f, "Integer constant too large: {}", digits),
+            LexErrorKind::UnterminatedString => write!(f, "Unterminated string constant"),
+            LexErrorKind::EofInString => write!(f, "EOF in string constant"),
+            LexErrorKind::NullInString => write!(f, "String contains null character."),
+            LexErrorKind::EscapedNullInString => write!(f, "String contains escaped null character."),
+            LexErrorKind::StringTooLong => write!(f, "String constant too long"),
+            LexErrorKind::EofInComment => write!(f, "EOF in comment"),
+            LexErrorKind::UnmatchedCommentEnd => write!(f, "Unmatched *)")
+        }
+    }
+}
+
+impl LexErrorKind {
+    // The text of the ERROR token in a token dump. The reference lexer gives
+    // just the character for an invalid one, and the message otherwise.
+    pub fn dump_text(&self) -> String {
+        match *self {
+            LexErrorKind::InvalidChar(ch) => ch.to_string(),
+            _ => self.to_string()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span
+}
+
+// Returned when a file has lexical errors. `tokens` is the complete token
+// stream, with an Error token wherever something went wrong, so that
+// callers can still dump or parse it.
+#[derive(Debug)]
+pub struct LexErrors {
+    pub tokens: Vec<Token>,
+    pub errors: Vec<LexError>
 }
 
 #[derive(PartialEq)]
@@ -58,6 +176,7 @@ enum LexerState {
     EqualsOrArrow,
     CommentOrMinus,
     CommentOrParens,
+    TimesOrCommentEnd,
     SingleLineComment,
     MultiLineComment,
     MultiLineCommentEnd,
@@ -65,33 +184,78 @@ enum LexerState {
     StringEscape
 }
 
-pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
+// A comment's text, delimiters included.
+#[derive(Debug, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    // The comments between the previous token and this one. Only filled in
+    // by lex_with_comments; the end of file token gets any at the end.
+    pub comments: Vec<Comment>
+}
+
+fn token(kind: TokenKind, file: FileId, start: (usize, usize), end: (usize, usize)) -> Token {
+    Token {
+        kind,
+        span: Span::new(file, start, end),
+        comments: Vec::new()
+    }
+}
+
+pub fn lex(chars: Peekable<Chars>, file: FileId) -> Result<Vec<Token>, LexErrors> {
+    lex_tokens(chars, file, false)
+}
+
+// Lexes the same tokens as `lex`, but keeps the comments, for tools that
+// write source back out.
+pub fn lex_with_comments(chars: Peekable<Chars>, file: FileId) -> Result<Vec<Token>, LexErrors> {
+    lex_tokens(chars, file, true)
+}
+
+fn lex_tokens(mut chars: Peekable<Chars>, file: FileId, keep_comments: bool) -> Result<Vec<Token>, LexErrors> {
     let mut state = LexerState::Start;
-    let mut cur_token = String::with_capacity(1024);
-    let mut line_no = 0;
+    let mut cur_token = String::with_capacity(MAX_STRING_LENGTH);
+    // The first thing wrong with the string literal we're in, if anything.
+    // We keep lexing to the closing quote and report it there.
+    let mut string_error = None;
     let mut use_char;
     let mut comment_depth = 0;
+    // The text of the comment we're in, while we're in one.
+    let mut comment: Option<String> = None;
+    let mut comments: Vec<Comment> = Vec::new();
     let mut tokens: Vec<Token> = Vec::new();
 
+    // Position of the character we're currently looking at, and of the
+    // first character of the token we're in the middle of.
+    let mut line = 1;
+    let mut col = 1;
+    let mut start = (1, 1);
+
     loop {
         use_char = true;
+        let here = (line, col);
+        let next = (line, col + 1);
         match chars.peek() {
             Some(ch) => {
                 match state {
                     LexerState::Start => {
+                        start = here;
                         match *ch {
-                            'a' ... 'z' | 'A' ... 'Z' => {
+                            'a' ..= 'z' | 'A' ..= 'Z' => {
                                 state = LexerState::Identifier;
                                 cur_token.push(*ch);
                             },
-                            '0' ... '9' => {
+                            '0' ..= '9' => {
                                 state = LexerState::Number;
                                 cur_token.push(*ch);
                             },
-                            '\n' => {
-                                line_no += 1;
-                            },
-                            ' ' | '\r' | '\t' => {
+                            ' ' | '\n' | '\r' | '\t' | '\x0b' | '\x0c' => {
                                 // Nothing
                             },
                             '<' => {
@@ -109,124 +273,61 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                             '"' => {
                                 state = LexerState::String;
                             },
-                            '{' => { tokens.push(Token::LeftBrace); },
-                            '}' => { tokens.push(Token::RightBrace); },
-                            ')' => { tokens.push(Token::RightParen); },
-                            ':' => { tokens.push(Token::Colon); },
-                            ';' => { tokens.push(Token::Semicolon); },
-                            '.' => { tokens.push(Token::Dot); },
-                            ',' => { tokens.push(Token::Comma); },
-                            '@' => { tokens.push(Token::At); },
-                            '+' => { tokens.push(Token::Plus); },
-                            '*' => { tokens.push(Token::Times); },
-                            '/' => { tokens.push(Token::Divide); },
-                            '~' => { tokens.push(Token::Tilde); },
+                            '*' => {
+                                state = LexerState::TimesOrCommentEnd;
+                            },
+                            '{' => { tokens.push(token(TokenKind::LeftBrace, file, here, next)); },
+                            '}' => { tokens.push(token(TokenKind::RightBrace, file, here, next)); },
+                            ')' => { tokens.push(token(TokenKind::RightParen, file, here, next)); },
+                            ':' => { tokens.push(token(TokenKind::Colon, file, here, next)); },
+                            ';' => { tokens.push(token(TokenKind::Semicolon, file, here, next)); },
+                            '.' => { tokens.push(token(TokenKind::Dot, file, here, next)); },
+                            ',' => { tokens.push(token(TokenKind::Comma, file, here, next)); },
+                            '@' => { tokens.push(token(TokenKind::At, file, here, next)); },
+                            '+' => { tokens.push(token(TokenKind::Plus, file, here, next)); },
+                            '/' => { tokens.push(token(TokenKind::Divide, file, here, next)); },
+                            '~' => { tokens.push(token(TokenKind::Tilde, file, here, next)); },
                             _ => {
-                                println!("Unexpected character: {}", ch);
-                                return tokens;
+                                tokens.push(token(TokenKind::Error(LexErrorKind::InvalidChar(*ch)), file, here, next));
                             }
                         }
                     },
                     LexerState::Identifier => {
                         match *ch {
-                            'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' | '_' => {
+                            'a' ..= 'z' | 'A' ..= 'Z' | '0' ..= '9' | '_' => {
                                 cur_token.push(*ch);
                             },
-                            '\n' => {
-                                state = LexerState::Start;
-                            },
-                            ' ' | '\r' | '\t' => {
-                                state = LexerState::Start;
-                            },
-                            '<' => {
-                                state = LexerState::Start;
-                            },
-                            '-' => {
-                                state = LexerState::Start;
-                            },
-                            '(' => {
-                                state = LexerState::Start;
-                            },
-                            '"' => {
-                                state = LexerState::String;
-                            },
-                            '=' | '~' | '/' | '*' | '+' | '@' | ',' | '.' | ';' | ':' | ')' | '}' | '{' => {
-                                state = LexerState::Start;
-                            },
                             _ => {
-                                println!("Unexpected character: {}", ch);
-                                return tokens;
+                                tokens.push(token(string_to_token(cur_token), file, start, here));
+                                cur_token = String::new();
+                                state = LexerState::Start;
+                                use_char = false;
                             }
                         }
-                        if state != LexerState::Identifier {
-                            tokens.push(string_to_token(cur_token));
-                            cur_token = String::new();
-                            use_char = false;
-                        }
                     },
                     LexerState::Number => {
                         match *ch {
-                            '0' ... '9' => {
+                            '0' ..= '9' => {
                                 cur_token.push(*ch);
                             },
-                            'a' ... 'z' | 'A' ... 'Z' | '_' => {
-                                state = LexerState::Start;
-                            }
-                            '\n' => {
-                                line_no += 1;
-                                state = LexerState::Start;
-                            },
-                            ' ' | '\r' | '\t' => {
-                                state = LexerState::Start;
-                            },
-                            '<' => {
-                                state = LexerState::Start;
-                            },
-                            '-' => {
-                                state = LexerState::Start;
-                            },
-                            '(' => {
-                                state = LexerState::Start;
-                            },
-                            '"' => {
-                                state = LexerState::Start;
-                            },
-                            '=' | '~' | '/' | '*' | '+' | '@' | ',' | '.' | ';' | ':' | ')' | '}' | '{' => {
-                                state = LexerState::Start;
-                            },
                             _ => {
-                                println!("Unexpected character: {}", ch);
-                            }
-                        }
-                        if state != LexerState::Number {
-                            let value = cur_token.parse::<i32>();
-                            match value {
-                                Ok(val) => {
-                                    tokens.push(Token::IntegerLiteral(val));
-                                },
-                                Err(err) => {
-                                    println!("{}", err);
-                                    return tokens;
-                                }
+                                tokens.push(token(number_to_token(cur_token), file, start, here));
+                                cur_token = String::new();
+                                state = LexerState::Start;
+                                use_char = false;
                             }
-                            cur_token = String::new();
-                            use_char = false;
                         }
                     },
                     LexerState::LessThan => {
                         match *ch {
                             '-' => {
-                                tokens.push(Token::Assign);
+                                tokens.push(token(TokenKind::Assign, file, start, next));
                             },
                             '=' => {
-                                tokens.push(Token::LessThanEqual);
-                            },
-                            '\n' => {
-                                tokens.push(Token::LessThan);
-                                line_no += 1;
+                                tokens.push(token(TokenKind::LessThanEqual, file, start, next));
                             },
                             _ => {
-                                tokens.push(Token::LessThan);
+                                tokens.push(token(TokenKind::LessThan, file, start, here));
                                 use_char = false;
                             }
                         }
@@ -236,38 +337,52 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                         match *ch {
                             '-' => {
                                 state = LexerState::SingleLineComment;
+                                comment = Some("-".to_string());
                             },
                             _ => {
-                                tokens.push(Token::Minus);
+                                tokens.push(token(TokenKind::Minus, file, start, here));
                                 state = LexerState::Start;
                                 use_char = false;
                             }
                         }
                     },
-                    LexerState::SingleLineComment => {
+                    LexerState::TimesOrCommentEnd => {
                         match *ch {
-                            '\n' => {
-                                line_no += 1;
-                                state = LexerState::Start;
+                            ')' => {
+                                tokens.push(token(TokenKind::Error(LexErrorKind::UnmatchedCommentEnd), file, start, next));
                             },
                             _ => {
-                                // Ignore
+                                tokens.push(token(TokenKind::Times, file, start, here));
+                                use_char = false;
+                            }
+                        }
+                        state = LexerState::Start;
+                    },
+                    LexerState::SingleLineComment => {
+                        if *ch == '\n' {
+                            if let Some(text) = comment.take() {
+                                comments.push(Comment { text, span: Span::new(file, start, here) });
                             }
+                            state = LexerState::Start;
                         }
                     },
                     LexerState::CommentOrParens => {
                         match *ch {
                             '*' => {
+                                if comment_depth == 0 {
+                                    comment = Some("(".to_string());
+                                }
                                 comment_depth += 1;
                                 state = LexerState::MultiLineComment;
                             },
                             _ => {
                                 if comment_depth == 0 {
-                                    tokens.push(Token::LeftParen);
+                                    tokens.push(token(TokenKind::LeftParen, file, start, here));
                                     state = LexerState::Start;
                                     use_char = false;
                                 } else {
                                     state = LexerState::MultiLineComment;
+                                    use_char = false;
                                 }
                             }
                         }
@@ -275,14 +390,10 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                     LexerState::EqualsOrArrow => {
                         match *ch {
                             '>' => {
-                                tokens.push(Token::Arrow);
-                            },
-                            '\n' => {
-                                tokens.push(Token::Equal);
-                                line_no += 1;
+                                tokens.push(token(TokenKind::Arrow, file, start, next));
                             },
                             _ => {
-                                tokens.push(Token::Equal);
+                                tokens.push(token(TokenKind::Equal, file, start, here));
                                 use_char = false;
                             }
                         }
@@ -293,8 +404,10 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                             '*' => {
                                 state = LexerState::MultiLineCommentEnd;
                             },
-                            _ => {
-                            }
+                            '(' => {
+                                state = LexerState::CommentOrParens;
+                            },
+                            _ => {}
                         }
                     },
                     LexerState::MultiLineCommentEnd => {
@@ -302,9 +415,14 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                             ')' => {
                                 comment_depth -= 1;
                                 if comment_depth == 0 {
+                                    if let Some(mut text) = comment.take() {
+                                        text.push(')');
+                                        comments.push(Comment { text, span: Span::new(file, start, next) });
+                                    }
                                     state = LexerState::Start;
                                 } else {
                                     state = LexerState::MultiLineComment;
+                                    use_char = false;
                                 }
                             },
                             '*' => {
@@ -312,6 +430,7 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                             },
                             _ => {
                                 state = LexerState::MultiLineComment;
+                                use_char = false;
                             }
                         }
                     },
@@ -321,95 +440,443 @@ pub fn lex(mut chars: Peekable<Chars>) -> Vec<Token> {
                                 state = LexerState::StringEscape;
                             },
                             '"' => {
-                                tokens.push(Token::StringLiteral(Rc::new(cur_token)));
+                                let kind = match string_error.take() {
+                                    Some(error) => TokenKind::Error(error),
+                                    None => TokenKind::StringLiteral(Symbol::string(&cur_token))
+                                };
+                                tokens.push(token(kind, file, start, next));
                                 cur_token = String::new();
                                 state = LexerState::Start;
                             },
                             '\n' => {
-                                println!("Error: newline in string literal.");
-                                return tokens;
+                                // An unescaped newline ends the string. If it
+                                // was already bad we report that, otherwise we
+                                // assume the closing quote was forgotten. Either
+                                // way lexing picks back up on the next line.
+                                let error = string_error.take().unwrap_or(LexErrorKind::UnterminatedString);
+                                tokens.push(token(TokenKind::Error(error), file, start, (line + 1, 1)));
+                                cur_token = String::new();
+                                state = LexerState::Start;
+                            },
+                            '\0' => {
+                                string_error = string_error.or(Some(LexErrorKind::NullInString));
                             },
                             _ => {
-                                cur_token.push(*ch);
+                                push_string_char(&mut cur_token, &mut string_error, *ch);
                             }
                         }
                     },
                     LexerState::StringEscape => {
                         match *ch {
-                            't' => {
-                                cur_token.push('\t');
-                            },
-                            'n' => {
-                                cur_token.push('\n');
-                            },
-                            '\n' => {
-                            },
-                            _ => {
-                                cur_token.push(*ch);
-                            }
+                            'b' => push_string_char(&mut cur_token, &mut string_error, '\x08'),
+                            't' => push_string_char(&mut cur_token, &mut string_error, '\t'),
+                            'n' => push_string_char(&mut cur_token, &mut string_error, '\n'),
+                            'f' => push_string_char(&mut cur_token, &mut string_error, '\x0c'),
+                            '\0' => {
+                                string_error = string_error.or(Some(LexErrorKind::EscapedNullInString));
+                            },
+                            // Any other escaped character, including a
+                            // newline, stands for itself.
+                            _ => push_string_char(&mut cur_token, &mut string_error, *ch)
                         }
                         state = LexerState::String;
                     }
                 }
             },
             None => {
-                if state == LexerState::MultiLineComment {
-                    println!("Error: EOF in comment.");
-                } else if state == LexerState::String {
-                    println!("Error: EOF in string literal.");
+                match state {
+                    LexerState::Identifier => {
+                        tokens.push(token(string_to_token(cur_token), file, start, here));
+                    },
+                    LexerState::Number => {
+                        tokens.push(token(number_to_token(cur_token), file, start, here));
+                    },
+                    LexerState::LessThan => {
+                        tokens.push(token(TokenKind::LessThan, file, start, here));
+                    },
+                    LexerState::CommentOrMinus => {
+                        tokens.push(token(TokenKind::Minus, file, start, here));
+                    },
+                    LexerState::CommentOrParens if comment_depth == 0 => {
+                        tokens.push(token(TokenKind::LeftParen, file, start, here));
+                    },
+                    LexerState::EqualsOrArrow => {
+                        tokens.push(token(TokenKind::Equal, file, start, here));
+                    },
+                    LexerState::TimesOrCommentEnd => {
+                        tokens.push(token(TokenKind::Times, file, start, here));
+                    },
+                    LexerState::CommentOrParens |
+                    LexerState::MultiLineComment |
+                    LexerState::MultiLineCommentEnd => {
+                        tokens.push(token(TokenKind::Error(LexErrorKind::EofInComment), file, start, here));
+                    },
+                    LexerState::String | LexerState::StringEscape => {
+                        // Whatever else was wrong with the string, running
+                        // into the end of the file is the error we report.
+                        tokens.push(token(TokenKind::Error(LexErrorKind::EofInString), file, start, here));
+                    },
+                    LexerState::SingleLineComment => {
+                        if let Some(text) = comment.take() {
+                            comments.push(Comment { text, span: Span::new(file, start, here) });
+                        }
+                    },
+                    _ => {}
                 }
                 break;
             }
         }
         if use_char {
-            let _ = chars.next();
+            let ch = chars.next();
+            if let (Some(ref mut text), Some(ch)) = (comment.as_mut(), ch) {
+                text.push(ch);
+            }
+            if let Some('\n') = ch {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+    }
+    tokens.push(token(TokenKind::Eof, file, (line, col), (line, col)));
+    if keep_comments {
+        attach_comments(&mut tokens, comments);
+    }
+
+    let errors: Vec<LexError> = tokens.iter().filter_map(|token| {
+        match token.kind {
+            TokenKind::Error(ref kind) => Some(LexError {
+                kind: kind.clone(),
+                span: token.span
+            }),
+            _ => None
         }
+    }).collect();
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(LexErrors { tokens, errors })
     }
-    tokens.push(Token::EOF);
-    return tokens;
 }
 
-fn string_to_token(chars: String) -> Token {
-    let lowercase = chars.chars().nth(0).unwrap().is_lowercase();
-    let copy = chars.clone();
-    match chars.as_ref() {
-        "case"     => Token::Case,
-        "class"    => Token::Class,
-        "else"     => Token::Else,
-        "esac"     => Token::Esac,
-        "fi"       => Token::Fi,
-        "if"       => Token::If,
-        "in"       => Token::In,
-        "inherits" => Token::Inherits,
-        "isvoid"   => Token::Isvoid,
-        "let"      => Token::Let,
-        "loop"     => Token::Loop,
-        "new"      => Token::New,
-        "not"      => Token::Not,
-        "of"       => Token::Of,
-        "pool"     => Token::Pool,
-        "then"     => Token::Then,
-        "while"    => Token::While,
-        "true"     => {
-            if lowercase {
-                Token::True
-            } else {
-                Token::Type(Rc::new(copy))
-            }
-        },
-        "false" => {
-            if lowercase {
-                Token::False
-            } else {
-                Token::Type(Rc::new(copy))
-            }
-        },
+// Gives each token the comments that come before it. Both lists are in
+// source order, and the last token is always the end of file.
+fn attach_comments(tokens: &mut [Token], comments: Vec<Comment>) {
+    let mut tokens = tokens.iter_mut();
+    let mut current = tokens.next().unwrap();
+    for comment in comments {
+        while (current.span.start_line, current.span.start_col) < (comment.span.end_line, comment.span.end_col) {
+            current = tokens.next().unwrap();
+        }
+        current.comments.push(comment);
+    }
+}
+
+// Adds a character to the string literal being lexed, noting if that
+// makes it too long. Once a string has gone wrong we stop collecting it.
+fn push_string_char(string: &mut String, error: &mut Option<LexErrorKind>, ch: char) {
+    if error.is_some() {
+        return;
+    }
+    if string.len() + ch.len_utf8() > MAX_STRING_LENGTH {
+        *error = Some(LexErrorKind::StringTooLong);
+    } else {
+        string.push(ch);
+    }
+}
+
+// The digits go in the int table as they were written, once we know
+// they fit in an Int.
+fn number_to_token(digits: String) -> TokenKind {
+    match digits.parse::<i32>() {
+        Ok(_) => TokenKind::IntegerLiteral(Symbol::int(&digits)),
+        Err(_) => TokenKind::Error(LexErrorKind::IntOverflow(digits))
+    }
+}
+
+// Keywords are case insensitive, except that the first letter of `true`
+// and `false` has to be lower case; `True` is a type name. Anything else
+// is a type if it starts with an upper case letter, otherwise an object.
+fn string_to_token(chars: String) -> TokenKind {
+    let lowercase = chars.chars().next().unwrap().is_lowercase();
+    match chars.to_ascii_lowercase().as_ref() {
+        "case"     => TokenKind::Case,
+        "class"    => TokenKind::Class,
+        "else"     => TokenKind::Else,
+        "esac"     => TokenKind::Esac,
+        "fi"       => TokenKind::Fi,
+        "if"       => TokenKind::If,
+        "in"       => TokenKind::In,
+        "inherits" => TokenKind::Inherits,
+        "isvoid"   => TokenKind::Isvoid,
+        "let"      => TokenKind::Let,
+        "loop"     => TokenKind::Loop,
+        "new"      => TokenKind::New,
+        "not"      => TokenKind::Not,
+        "of"       => TokenKind::Of,
+        "pool"     => TokenKind::Pool,
+        "then"     => TokenKind::Then,
+        "while"    => TokenKind::While,
+        "true" if lowercase => TokenKind::True,
+        "false" if lowercase => TokenKind::False,
         _ => {
             if lowercase {
-                Token::Identifier(Rc::new(copy))
+                TokenKind::Identifier(Symbol::id(&chars))
             } else {
-                Token::Type(Rc::new(copy))
+                TokenKind::Type(Symbol::id(&chars))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use source::FileId;
+    use super::*;
+
+    fn lex_word(word: &str) -> TokenKind {
+        let mut tokens = lex(word.chars().peekable(), FileId(0)).unwrap();
+        assert_eq!(tokens.len(), 2, "{} lexed to {:?}", word, tokens);
+        tokens.remove(0).kind
+    }
+
+    // Every way of writing a word we check a keyword against.
+    fn casings(word: &str) -> Vec<String> {
+        let alternating: String = word.chars().enumerate().map(|(i, ch)| {
+            if i % 2 == 0 { ch.to_ascii_uppercase() } else { ch }
+        }).collect();
+        let inverted: String = word.chars().enumerate().map(|(i, ch)| {
+            if i % 2 == 1 { ch.to_ascii_uppercase() } else { ch }
+        }).collect();
+        let mut capitalized = word[..1].to_ascii_uppercase();
+        capitalized.push_str(&word[1..]);
+        vec![word.to_string(), word.to_ascii_uppercase(), capitalized, alternating, inverted]
+    }
+
+    #[test]
+    fn keywords_are_case_insensitive() {
+        let keywords = [
+            ("case", TokenKind::Case),
+            ("class", TokenKind::Class),
+            ("else", TokenKind::Else),
+            ("esac", TokenKind::Esac),
+            ("fi", TokenKind::Fi),
+            ("if", TokenKind::If),
+            ("in", TokenKind::In),
+            ("inherits", TokenKind::Inherits),
+            ("isvoid", TokenKind::Isvoid),
+            ("let", TokenKind::Let),
+            ("loop", TokenKind::Loop),
+            ("new", TokenKind::New),
+            ("not", TokenKind::Not),
+            ("of", TokenKind::Of),
+            ("pool", TokenKind::Pool),
+            ("then", TokenKind::Then),
+            ("while", TokenKind::While),
+        ];
+        for &(keyword, ref expected) in &keywords {
+            for word in casings(keyword) {
+                assert_eq!(lex_word(&word), *expected, "lexing {}", word);
             }
         }
     }
+
+    #[test]
+    fn booleans_must_start_lower_case() {
+        let cases = [
+            ("true", TokenKind::True),
+            ("tRUE", TokenKind::True),
+            ("tRuE", TokenKind::True),
+            ("True", TokenKind::Type(Symbol::id("True"))),
+            ("TRUE", TokenKind::Type(Symbol::id("TRUE"))),
+            ("false", TokenKind::False),
+            ("fALSE", TokenKind::False),
+            ("fAlSe", TokenKind::False),
+            ("False", TokenKind::Type(Symbol::id("False"))),
+            ("FaLsE", TokenKind::Type(Symbol::id("FaLsE"))),
+        ];
+        for &(word, ref expected) in &cases {
+            assert_eq!(lex_word(word), *expected, "lexing {}", word);
+        }
+    }
+
+    #[test]
+    fn words_containing_keywords_are_names() {
+        let cases = [
+            ("classes", TokenKind::Identifier(Symbol::id("classes"))),
+            ("Classy", TokenKind::Type(Symbol::id("Classy"))),
+            ("if_", TokenKind::Identifier(Symbol::id("if_"))),
+            ("IF2", TokenKind::Type(Symbol::id("IF2"))),
+            ("trueish", TokenKind::Identifier(Symbol::id("trueish"))),
+            ("notX", TokenKind::Identifier(Symbol::id("notX"))),
+        ];
+        for &(word, ref expected) in &cases {
+            assert_eq!(lex_word(word), *expected, "lexing {}", word);
+        }
+    }
+
+    fn lex_errors(text: &str) -> LexErrors {
+        lex(text.chars().peekable(), FileId(0)).err().unwrap()
+    }
+
+    fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+        Span::new(FileId(0), start, end)
+    }
+
+    #[test]
+    fn keeps_lexing_after_an_error() {
+        let failure = lex_errors("x *) y\n2147483648 2147483647 # z\n(* (* *) open");
+        let errors: Vec<(LexErrorKind, Span)> = failure.errors.iter().map(|error| (error.kind.clone(), error.span)).collect();
+        assert_eq!(errors, vec![
+            (LexErrorKind::UnmatchedCommentEnd, span((1, 3), (1, 5))),
+            (LexErrorKind::IntOverflow("2147483648".to_string()), span((2, 1), (2, 11))),
+            (LexErrorKind::InvalidChar('#'), span((2, 23), (2, 24))),
+            (LexErrorKind::EofInComment, span((3, 1), (3, 14)))
+        ]);
+        let kinds: Vec<&TokenKind> = failure.tokens.iter().map(|token| &token.kind).collect();
+        assert_eq!(kinds, vec![
+            &TokenKind::Identifier(Symbol::id("x")),
+            &TokenKind::Error(LexErrorKind::UnmatchedCommentEnd),
+            &TokenKind::Identifier(Symbol::id("y")),
+            &TokenKind::Error(LexErrorKind::IntOverflow("2147483648".to_string())),
+            &TokenKind::IntegerLiteral(Symbol::int("2147483647")),
+            &TokenKind::Error(LexErrorKind::InvalidChar('#')),
+            &TokenKind::Identifier(Symbol::id("z")),
+            &TokenKind::Error(LexErrorKind::EofInComment),
+            &TokenKind::Eof
+        ]);
+    }
+
+    // The kinds of the tokens in some text, leaving off the end of file.
+    fn kinds(text: &str) -> Vec<TokenKind> {
+        let tokens = match lex(text.chars().peekable(), FileId(0)) {
+            Ok(tokens) => tokens,
+            Err(failure) => failure.tokens
+        };
+        let mut kinds: Vec<TokenKind> = tokens.into_iter().map(|token| token.kind).collect();
+        assert_eq!(kinds.pop(), Some(TokenKind::Eof));
+        kinds
+    }
+
+    fn string(text: &str) -> TokenKind {
+        TokenKind::StringLiteral(Symbol::string(text))
+    }
+
+    #[test]
+    fn processes_escapes_in_strings() {
+        let cases = [
+            ("\"a\\bb\\tc\\nd\\fe\"", "a\x08b\tc\nd\x0ce"),
+            ("\"\\a\\q\\\\\\\"\"", "aq\\\""),
+            // An escaped newline is a newline in the string.
+            ("\"one\\\ntwo\"", "one\ntwo"),
+            ("\"tab\tand\x0bvtab\"", "tab\tand\x0bvtab"),
+        ];
+        for &(text, expected) in &cases {
+            assert_eq!(kinds(text), vec![string(expected)], "lexing {:?}", text);
+        }
+
+        let tokens = lex("\"one\\\ntwo\" x".chars().peekable(), FileId(0)).unwrap();
+        assert_eq!(tokens[0].span, span((1, 1), (2, 5)));
+        assert_eq!(tokens[1].span, span((2, 6), (2, 7)));
+    }
+
+    #[test]
+    fn limits_the_length_of_strings() {
+        let longest = "a".repeat(MAX_STRING_LENGTH);
+        assert_eq!(kinds(&format!("\"{}\"", longest)), vec![string(&longest)]);
+        // An escape counts as the one character it stands for.
+        let escaped = format!("\"{}\\n\"", &longest[1..]);
+        assert_eq!(kinds(&escaped), vec![string(&format!("{}\n", &longest[1..]))]);
+
+        let failure = lex_errors(&format!("\"{}a\" x", longest));
+        assert_eq!(failure.errors[0].kind, LexErrorKind::StringTooLong);
+        assert_eq!(failure.errors[0].span, span((1, 1), (1, MAX_STRING_LENGTH + 4)));
+        assert_eq!(failure.tokens[1].kind, TokenKind::Identifier(Symbol::id("x")));
+    }
+
+    #[test]
+    fn reports_bad_strings_and_carries_on() {
+        let x = || TokenKind::Identifier(Symbol::id("x"));
+        let cases = [
+            // A bad string is reported at its closing quote, and lexing
+            // starts again after it.
+            ("\"a\0b\" x", vec![TokenKind::Error(LexErrorKind::NullInString), x()]),
+            ("\"a\\\0b\" x", vec![TokenKind::Error(LexErrorKind::EscapedNullInString), x()]),
+            // Only the first thing wrong with a string is reported.
+            ("\"\0\\\0\" x", vec![TokenKind::Error(LexErrorKind::NullInString), x()]),
+            // An unescaped newline ends the string, so x is a name on the
+            // next line rather than part of a string ending at its quote.
+            ("\"abc\nx\"", vec![TokenKind::Error(LexErrorKind::UnterminatedString), x(),
+                                 TokenKind::Error(LexErrorKind::EofInString)]),
+            ("\"a\0\nx", vec![TokenKind::Error(LexErrorKind::NullInString), x()]),
+            ("x \"abc", vec![x(), TokenKind::Error(LexErrorKind::EofInString)]),
+            ("x \"abc\\", vec![x(), TokenKind::Error(LexErrorKind::EofInString)]),
+        ];
+        for &(text, ref expected) in &cases {
+            assert_eq!(kinds(text), *expected, "lexing {:?}", text);
+        }
+
+        let failure = lex_errors("\"abc\nx \"de");
+        assert_eq!(failure.errors[0].span, span((1, 1), (2, 1)));
+        assert_eq!(failure.errors[1].span, span((2, 3), (2, 6)));
+    }
+
+    #[test]
+    fn describes_invalid_characters() {
+        let error = LexErrorKind::InvalidChar('#');
+        assert_eq!(error.to_string(), "Invalid character '#'");
+        assert_eq!(error.dump_text(), "#");
+        assert_eq!(LexErrorKind::InvalidChar('\x01').to_string(), "Invalid character '\\u{1}'");
+        assert_eq!(LexErrorKind::EofInComment.dump_text(), "EOF in comment");
+    }
+
+    #[test]
+    fn spans_each_token() {
+        let text = "class A {\n  x <- 10; -- c\n  (* a\n b *) y=>z <= \"s\\\n\\\nt\"\n}";
+        let tokens = lex(text.chars().peekable(), FileId(0)).unwrap();
+        let spans: Vec<((usize, usize), (usize, usize))> = tokens.iter().map(|token| {
+            ((token.span.start_line, token.span.start_col), (token.span.end_line, token.span.end_col))
+        }).collect();
+        assert_eq!(spans, vec![
+            ((1, 1), (1, 6)),   // class
+            ((1, 7), (1, 8)),   // A
+            ((1, 9), (1, 10)),  // {
+            ((2, 3), (2, 4)),   // x
+            ((2, 5), (2, 7)),   // <-
+            ((2, 8), (2, 10)),  // 10
+            ((2, 10), (2, 11)), // ;
+            ((4, 7), (4, 8)),   // y
+            ((4, 8), (4, 10)),  // =>
+            ((4, 10), (4, 11)), // z
+            ((4, 12), (4, 14)), // <=
+            ((4, 15), (6, 3)),  // the string, which runs over two escaped newlines
+            ((7, 1), (7, 2)),   // }
+            ((7, 2), (7, 2))    // the end of file
+        ]);
+    }
+
+    #[test]
+    fn keeps_comments_on_the_next_token() {
+        let text = "-- one\nx (* two (* nested *) *)(* three *) y -- four";
+        let tokens = lex_with_comments(text.chars().peekable(), FileId(0)).unwrap();
+        let comments: Vec<Vec<&str>> = tokens.iter().map(|token| {
+            token.comments.iter().map(|comment| &comment.text[..]).collect()
+        }).collect();
+        assert_eq!(comments, vec![
+            vec!["-- one"],
+            vec!["(* two (* nested *) *)", "(* three *)"],
+            vec!["-- four"]
+        ]);
+        assert_eq!(tokens[1].comments[0].span, Span::new(FileId(0), (2, 3), (2, 25)));
+        assert_eq!(tokens[0].comments[0].span, Span::new(FileId(0), (1, 1), (1, 7)));
+        assert_eq!(tokens[2].comments[0].span, Span::new(FileId(0), (2, 39), (2, 46)));
+
+        // A multi-line comment's span ends on the line its `*)` is on.
+        let tokens = lex_with_comments("(* a\n *) x".chars().peekable(), FileId(0)).unwrap();
+        assert_eq!(tokens[0].comments[0].span, Span::new(FileId(0), (1, 1), (2, 4)));
+
+        let tokens = lex(text.chars().peekable(), FileId(0)).unwrap();
+        assert!(tokens.iter().all(|token| token.comments.is_empty()));
+    }
 }
@@ -1,26 +1,462 @@
-use std::io::prelude::*;
+use std::env;
 use std::fs::File;
-use std::rc::Rc;
-use std::boxed::Box;
+use std::io;
+use std::io::BufWriter;
+use std::io::prelude::*;
+use std::process;
 
-use ast::*;
-use printer::Printable;
+use source::{SourceFile, SourceMap};
 
 mod ast;
-mod parser;
+mod codegen;
+mod formatter;
+mod json;
 mod lexer;
+mod parser;
 mod printer;
+mod semant;
+mod source;
+mod symbol;
+mod tree;
+
+// The phases of the compiler, in the order they run. Passing one of
+// these on the command line stops the compiler after that phase and
+// dumps its output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    Lex,
+    Parse,
+    Semant,
+    Codegen
+}
+
+// What to do with the input files: compile them, or reformat them in
+// place, or just check that they're formatted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Compile,
+    Format,
+    CheckFormat
+}
+
+struct Options {
+    command: Command,
+    stage: Stage,
+    trace_parser: bool,
+    recursive_descent: bool,
+    json: bool,
+    // Where to write the dump; standard output if not given.
+    output: Option<String>,
+    files: Vec<String>
+}
+
+fn usage() -> String {
+    [
+        "usage: cool [--lex | --parse | --semant | --codegen] [--trace-parser]",
+        "            [--recursive-descent] [--json] [-o FILE] FILE.cl...",
+        "       cool fmt [--check] [--recursive-descent] FILE.cl...",
+        "",
+        "  --lex            stop after lexing and dump the tokens of each file",
+        "  --parse          stop after parsing and dump the program tree (default)",
+        "  --semant         stop after semantic analysis and dump the checked tree",
+        "  --codegen        stop after code generation",
+        "  --trace-parser   describe each step of the parser on stderr",
+        "  --recursive-descent",
+        "                   parse with the recursive-descent parser instead of",
+        "                   the generated one",
+        "  --json           with --parse, dump the program tree as JSON",
+        "  -o FILE          write the dump to FILE instead of standard output",
+        "",
+        "  fmt              rewrite each file in the standard layout",
+        "  --check          with fmt, list the files that aren't laid out that",
+        "                   way instead of changing them",
+        "",
+        "Input files ending in .json are read as program trees dumped by --json,",
+        "and files starting with _program as trees in the reference parser's",
+        "format, like the ones --parse dumps.",
+    ].join("\n")
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let (mut command, args) = match args.split_first() {
+        Some((first, rest)) if first == "fmt" => (Command::Format, rest),
+        _ => (Command::Compile, args)
+    };
+    let mut stage = None;
+    let mut trace_parser = false;
+    let mut recursive_descent = false;
+    let mut json = false;
+    let mut output = None;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let new_stage = match arg.as_ref() {
+            "--lex" => Stage::Lex,
+            "--parse" => Stage::Parse,
+            "--semant" => Stage::Semant,
+            "--codegen" => Stage::Codegen,
+            "--trace-parser" => {
+                trace_parser = true;
+                continue;
+            },
+            "--recursive-descent" => {
+                recursive_descent = true;
+                continue;
+            },
+            "--json" if command == Command::Compile => {
+                json = true;
+                continue;
+            },
+            "-o" if command == Command::Compile => {
+                match args.next() {
+                    Some(path) => output = Some(path.clone()),
+                    None => return Err(format!("-o needs a file name\n\n{}", usage()))
+                }
+                continue;
+            },
+            "--check" if command != Command::Compile => {
+                command = Command::CheckFormat;
+                continue;
+            },
+            "-h" | "--help" => return Err(usage()),
+            _ => {
+                if arg.starts_with('-') {
+                    return Err(format!("unknown option '{}'\n\n{}", arg, usage()));
+                }
+                files.push(arg.clone());
+                continue;
+            }
+        };
+        if command != Command::Compile {
+            return Err(format!("'{}' can't be used with fmt\n\n{}", arg, usage()));
+        }
+        if stage.is_some() {
+            return Err(format!("only one of --lex, --parse, --semant and --codegen may be given\n\n{}", usage()));
+        }
+        stage = Some(new_stage);
+    }
+
+    if files.is_empty() {
+        return Err(format!("no input files\n\n{}", usage()));
+    }
+    let stage = stage.unwrap_or(Stage::Parse);
+    if json && stage != Stage::Parse {
+        return Err(format!("--json can only be used with --parse\n\n{}", usage()));
+    }
+
+    Ok(Options {
+        command,
+        stage,
+        trace_parser,
+        recursive_descent,
+        json,
+        output,
+        files
+    })
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|err| format!("cannot read '{}': {}", path, err))?;
+    Ok(contents)
+}
+
+// Writes a dump to the file given with -o, or to standard output.
+fn dump<F>(options: &Options, write: F) -> Result<(), String>
+    where F: FnOnce(&mut dyn Write) -> io::Result<()>
+{
+    let result = match options.output {
+        Some(ref path) => File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write(&mut out)?;
+            out.flush()
+        }),
+        None => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            write(&mut out).and_then(|_| out.flush())
+        }
+    };
+    let name = options.output.as_ref().map_or("standard output", |path| &path[..]);
+    result.map_err(|err| format!("cannot write '{}': {}", name, err))
+}
+
+fn write_file(path: &str, contents: &str) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(|err| format!("cannot write '{}': {}", path, err))
+}
+
+fn parse(tokens: &[lexer::Token], options: &Options) -> Result<ast::Program, parser::ParseErrors> {
+    if options.recursive_descent {
+        parser::descent::parse_cool_program(tokens)
+    } else {
+        parser::parse_cool_program(tokens, options.trace_parser)
+    }
+}
+
+// Reformats each file in place, or with --check lists the ones that
+// would change. Files with errors are reported and left alone.
+fn format_files(options: &Options) -> Result<(), String> {
+    let mut sources = SourceMap::new();
+    let mut failed = false;
+    let mut unformatted = 0;
+    for path in &options.files {
+        let text = read_file(path)?;
+        let file = sources.add(path.clone(), text);
+        let text = &sources.file(file).text;
+
+        let tokens = match lexer::lex_with_comments(text.chars().peekable(), file) {
+            Ok(tokens) => tokens,
+            Err(failure) => {
+                for error in &failure.errors {
+                    eprintln!("{}: {}", sources.location(error.span), error.kind);
+                }
+                failed = true;
+                continue;
+            }
+        };
+        let program = match parse(&tokens, options) {
+            Ok(program) => program,
+            Err(failure) => {
+                for error in &failure.errors {
+                    eprintln!("{}: {}", sources.location(error.span), error.message);
+                    eprintln!("{}", sources.snippet(error.span));
+                }
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = match formatter::format_program(&program, &tokens) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{}: {}", sources.location(error.span), error.message);
+                eprintln!("{}", sources.snippet(error.span));
+                failed = true;
+                continue;
+            }
+        };
+        if formatted != *text {
+            if options.command == Command::CheckFormat {
+                println!("{}", path);
+                unformatted += 1;
+            } else {
+                write_file(path, &formatted)?;
+            }
+        }
+    }
+
+    if failed {
+        Err("some files could not be formatted because of errors".to_string())
+    } else if unformatted > 0 {
+        Err(format!("{} of {} files are not formatted", unformatted, options.files.len()))
+    } else {
+        Ok(())
+    }
+}
+
+// Whether an input file holds a program tree dumped with --json, rather
+// than Cool source.
+fn is_json(path: &str) -> bool {
+    path.ends_with(".json")
+}
+
+// Whether an input file holds a program tree rather than Cool source.
+fn is_tree(source: &SourceFile) -> bool {
+    is_json(&source.name) || tree::is_tree(&source.text)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    if options.command != Command::Compile {
+        return format_files(options);
+    }
 
+    let mut sources = SourceMap::new();
+    let mut files = Vec::new();
+    for path in &options.files {
+        let text = read_file(path)?;
+        files.push(sources.add(path.clone(), text));
+    }
+
+    // Lex everything up front. Lexical errors are reported for every file
+    // before we give up, and the --lex dump includes the ERROR tokens.
+    // Trees that were read in have nothing to lex.
+    let mut token_streams = Vec::new();
+    let mut lex_failed = false;
+    for &file in files.iter().filter(|&&file| !is_tree(sources.file(file))) {
+        let text = &sources.file(file).text;
+        let tokens = match lexer::lex(text.chars().peekable(), file) {
+            Ok(tokens) => tokens,
+            Err(failure) => {
+                for error in &failure.errors {
+                    eprintln!("{}: {}", sources.location(error.span), error.kind);
+                }
+                lex_failed = true;
+                failure.tokens
+            }
+        };
+        token_streams.push(tokens);
+    }
+
+    if options.stage == Stage::Lex {
+        dump(options, |out| {
+            for tokens in &token_streams {
+                let file = tokens[0].span.file;
+                printer::print_tokens(out, &sources.file(file).name, tokens)?;
+            }
+            Ok(())
+        })?;
+    }
+    if lex_failed {
+        return Err("compilation halted due to lexical errors".to_string());
+    }
+    if options.stage == Stage::Lex {
+        return Ok(());
+    }
+
+    // Every file contributes its classes to one program, just like
+    // passing several files to the reference compiler.
+    // Syntax errors in every file are reported before we give up.
+    let mut program: Option<ast::Program> = None;
+    let mut parse_failed = false;
+    let mut token_streams = token_streams.iter();
+    for &file in &files {
+        let source = sources.file(file);
+        let parsed = if is_json(&source.name) || tree::is_tree(&source.text) {
+            // Trees name the files their classes came from, which may add
+            // to the source map.
+            let (name, text) = (source.name.clone(), source.text.clone());
+            let read = if is_json(&name) {
+                json::parse(&text).and_then(|tree| json::program_from_json(&tree, &mut sources))
+            } else {
+                tree::read_program(&text, file, &mut sources)
+            };
+            read.map_err(|message| format!("{}: {}", name, message))
+        } else {
+            parse(token_streams.next().unwrap(), options).map_err(|failure| {
+                for error in &failure.errors {
+                    eprintln!("{}: {}", sources.location(error.span), error.message);
+                    eprintln!("{}", sources.snippet(error.span));
+                }
+                parse_failed = true;
+                failure.program
+            }).or_else(Ok)
+        };
+        let mut parsed = parsed?;
+        if let Some(previous) = program {
+            let mut classes = previous.classes;
+            classes.append(&mut parsed.classes);
+            parsed.classes = classes;
+        }
+        program = Some(parsed);
+    }
+    if parse_failed {
+        return Err("compilation halted due to parse errors".to_string());
+    }
+    let mut program = program.unwrap();
+
+    if options.stage == Stage::Parse {
+        return dump(options, |out| {
+            if options.json {
+                writeln!(out, "{}", json::program_to_json(&program, &sources))
+            } else {
+                printer::print_program(out, &program, &sources)
+            }
+        });
+    }
+
+    if let Err(errors) = semant::check_program(&mut program) {
+        for error in &errors {
+            eprintln!("{}: {}", sources.location(error.span), error.message);
+        }
+        return Err("compilation halted due to static semantic errors".to_string());
+    }
+
+    if options.stage == Stage::Semant {
+        return dump(options, |out| printer::print_program(out, &program, &sources));
+    }
+
+    // Only the constants are generated so far.
+    dump(options, |out| codegen::emit_constants(out))
+}
 
 fn main() {
-    let mut f = File::open("cool.c").unwrap();
-    let mut s = String::new();
-    let _ = f.read_to_string(&mut s);
-    let chars = s.chars().peekable();
-
-    let tokens = lexer::lex(chars);
-    let program = parser::parse_cool_program(&tokens);
-    if let Some(p) = program {
-        p.pretty_print(0);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("cool: {}", message);
+            process::exit(2);
+        }
+    };
+
+    if let Err(message) = run(&options) {
+        eprintln!("cool: {}", message);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Options, String> {
+        let args: Vec<String> = line.split_whitespace().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    // The first line of the error for a command line, without the usage.
+    fn error(line: &str) -> String {
+        parse_line(line).err().unwrap().lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn reads_options_and_files() {
+        let options = parse_line("a.cl b.cl").ok().unwrap();
+        assert_eq!(options.command, Command::Compile);
+        assert_eq!(options.stage, Stage::Parse);
+        assert!(!options.trace_parser && !options.recursive_descent && !options.json);
+        assert_eq!(options.output, None);
+        assert_eq!(options.files, vec!["a.cl", "b.cl"]);
+
+        let options = parse_line("--semant a.cl --trace-parser --recursive-descent -o out.txt").ok().unwrap();
+        assert_eq!(options.stage, Stage::Semant);
+        assert!(options.trace_parser && options.recursive_descent);
+        assert_eq!(options.output, Some("out.txt".to_string()));
+        assert_eq!(options.files, vec!["a.cl"]);
+
+        let options = parse_line("--json --parse a.cl").ok().unwrap();
+        assert!(options.json);
+
+        let options = parse_line("fmt --check a.cl").ok().unwrap();
+        assert_eq!(options.command, Command::CheckFormat);
+        assert_eq!(parse_line("fmt a.cl").ok().unwrap().command, Command::Format);
+    }
+
+    #[test]
+    fn rejects_bad_command_lines() {
+        let cases = [
+            ("--lex --semant a.cl", "only one of --lex, --parse, --semant and --codegen may be given"),
+            ("--parse --parse a.cl", "only one of --lex, --parse, --semant and --codegen may be given"),
+            ("--json a.cl --semant", "--json can only be used with --parse"),
+            ("--json --lex a.cl", "--json can only be used with --parse"),
+            ("a.cl -o", "-o needs a file name"),
+            ("fmt --lex a.cl", "'--lex' can't be used with fmt"),
+            ("fmt --json a.cl", "unknown option '--json'"),
+            ("--check a.cl", "unknown option '--check'"),
+            ("--lexx a.cl", "unknown option '--lexx'"),
+            ("--lex", "no input files"),
+            ("fmt", "no input files"),
+            ("", "no input files"),
+        ];
+        for &(line, expected) in &cases {
+            assert_eq!(error(line), expected, "parsing {:?}", line);
+        }
+        assert!(parse_line("a.cl --help").err().unwrap().starts_with("usage: cool"));
+        assert!(parse_line("--lexx a.cl").err().unwrap().ends_with(&usage()));
     }
 }
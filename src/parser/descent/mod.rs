@@ -0,0 +1,731 @@
+/*
+ * A recursive-descent parser, with Pratt-style precedence climbing for
+ * expressions. It builds the same trees as the table-driven parser and
+ * recovers from errors at the same places, so the two can be checked
+ * against each other.
+ */
+
+use ast;
+use lexer::Token;
+use lexer::TokenKind;
+use source::Span;
+use symbol;
+use symbol::Symbol;
+
+use super::{one_of, ParseError, ParseErrors, ERROR_IDENTIFIER};
+
+// How tightly each operator binds, loosest first. An operator's operands
+// are parsed at the next level up, or at the same level for `<-`, which
+// associates to the right.
+const ASSIGN: u8 = 1;
+const NOT: u8 = 2;
+const COMPARISON: u8 = 3;
+const SUM: u8 = 4;
+const PRODUCT: u8 = 5;
+const ISVOID: u8 = 6;
+const NEGATION: u8 = 7;
+const STATIC_DISPATCH: u8 = 8;
+const DISPATCH: u8 = 9;
+
+// Descriptions of the kinds of token that carry a value, and of the
+// tokens that can start an expression or follow one, for error messages.
+const TYPE: &str = "a type name";
+const IDENTIFIER: &str = "an identifier";
+const EXPRESSION: &str = "an expression";
+const OPERATOR: &str = "an operator";
+
+// The precedence of a binary or postfix operator.
+fn infix_precedence(kind: &TokenKind) -> Option<u8> {
+    match *kind {
+        TokenKind::LessThan | TokenKind::LessThanEqual | TokenKind::Equal => Some(COMPARISON),
+        TokenKind::Plus | TokenKind::Minus => Some(SUM),
+        TokenKind::Times | TokenKind::Divide => Some(PRODUCT),
+        TokenKind::At => Some(STATIC_DISPATCH),
+        TokenKind::Dot => Some(DISPATCH),
+        _ => None
+    }
+}
+
+fn starts_expression(kind: &TokenKind) -> bool {
+    matches!(*kind,
+             TokenKind::Identifier(_) | TokenKind::IntegerLiteral(_) | TokenKind::StringLiteral(_) |
+             TokenKind::True | TokenKind::False | TokenKind::If | TokenKind::While | TokenKind::Let |
+             TokenKind::Case | TokenKind::New | TokenKind::Isvoid | TokenKind::Not |
+             TokenKind::LeftBrace | TokenKind::LeftParen | TokenKind::Tilde)
+}
+
+// Returned once an error has been recorded, to unwind to the nearest
+// construct that can recover from it.
+struct Failed;
+
+type Parse<T> = Result<T, Failed>;
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    // Index of the token currently being looked at
+    position: usize,
+    errors: Vec<ParseError>
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &'a TokenKind {
+        let tokens = self.tokens;
+        &tokens[self.position].kind
+    }
+
+    fn advance(&mut self) {
+        self.position += 1;
+    }
+
+    // The span from the token at `start` up to the last one consumed.
+    fn span_from(&self, start: usize) -> Span {
+        if start == self.position {
+            self.tokens[start].span.empty_before()
+        } else {
+            self.tokens[start].span.to(self.tokens[self.position - 1].span)
+        }
+    }
+
+    // Reports that the current token isn't one of `expected`.
+    fn fail(&mut self, expected: &[&str]) -> Failed {
+        let message = format!("expected {} but found {}", one_of(expected), self.peek());
+        self.fail_with(message)
+    }
+
+    fn fail_with(&mut self, message: String) -> Failed {
+        let span = self.tokens[self.position].span;
+        self.errors.push(ParseError { span, message });
+        Failed
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Parse<()> {
+        if *self.peek() == kind {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.fail(&[&kind.to_string()]))
+        }
+    }
+
+    // Expects `kind` after an expression, which could also have been
+    // followed by an operator.
+    fn expect_after_expression(&mut self, kind: TokenKind) -> Parse<()> {
+        if *self.peek() == kind {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.fail(&[&kind.to_string(), OPERATOR]))
+        }
+    }
+
+    fn expect_type(&mut self) -> Parse<ast::Symbol> {
+        match *self.peek() {
+            TokenKind::Type(ref name) => {
+                self.advance();
+                Ok(*name)
+            },
+            _ => Err(self.fail(&[TYPE]))
+        }
+    }
+
+    // A type name where a type is expected, rather than a class name.
+    fn expect_cool_type(&mut self) -> Parse<ast::CoolType> {
+        self.expect_type().map(ast::CoolType::new)
+    }
+
+    fn expect_identifier(&mut self) -> Parse<ast::Symbol> {
+        match *self.peek() {
+            TokenKind::Identifier(ref name) => {
+                self.advance();
+                Ok(*name)
+            },
+            _ => Err(self.fail(&[IDENTIFIER]))
+        }
+    }
+
+    // Panic-mode recovery for the construct starting at the token at
+    // `first`: skips to the first token at the same nesting depth, at or
+    // after the current one, that is one of `resume`. Returns false, and
+    // skips nothing, if the construct ends first, so that an enclosing
+    // construct can try instead.
+    fn skip_to(&mut self, first: usize, resume: &[TokenKind]) -> bool {
+        let mut depth = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(first) {
+            if i >= self.position && depth == 0 {
+                if resume.contains(&token.kind) {
+                    self.position = i;
+                    return true;
+                }
+                if token.kind == TokenKind::Semicolon {
+                    return false;
+                }
+            }
+            match token.kind {
+                TokenKind::LeftParen | TokenKind::LeftBrace => depth += 1,
+                // A closer with no opener ends the construct
+                TokenKind::RightParen | TokenKind::RightBrace if depth == 0 => return false,
+                TokenKind::RightParen | TokenKind::RightBrace => depth -= 1,
+                TokenKind::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn program(&mut self) -> Vec<ast::Class> {
+        let mut classes = Vec::new();
+        let mut first = true;
+        loop {
+            let start = self.position;
+            let class = match *self.peek() {
+                TokenKind::Class => self.class(),
+                TokenKind::Eof if !first => break,
+                _ if first => Err(self.fail(&["'class'"])),
+                _ => Err(self.fail(&["end of file", "'class'"]))
+            };
+            first = false;
+            match class.and_then(|class| self.expect(TokenKind::Semicolon).map(|_| class)) {
+                Ok(class) => classes.push(class),
+                Err(Failed) => {
+                    if !self.skip_to(start, &[TokenKind::Semicolon]) {
+                        break;
+                    }
+                    self.advance();
+                }
+            }
+        }
+        classes
+    }
+
+    fn class(&mut self) -> Parse<ast::Class> {
+        let start = self.position;
+        self.expect(TokenKind::Class)?;
+        let name = self.expect_type()?;
+        let parent = match *self.peek() {
+            TokenKind::Inherits => {
+                self.advance();
+                self.expect_type()?
+            },
+            TokenKind::LeftBrace => symbol::OBJECT,
+            _ => return Err(self.fail(&["'inherits'", "'{'"]))
+        };
+        self.expect(TokenKind::LeftBrace)?;
+
+        let mut features = Vec::new();
+        while *self.peek() != TokenKind::RightBrace {
+            let feature_start = self.position;
+            let feature = match *self.peek() {
+                TokenKind::Identifier(_) => self.feature(),
+                _ => Err(self.fail(&[IDENTIFIER, "'}'"]))
+            };
+            match feature {
+                Ok(feature) => features.push(feature),
+                Err(failed) => {
+                    if !self.skip_to(feature_start, &[TokenKind::Semicolon]) {
+                        return Err(failed);
+                    }
+                    self.advance();
+                }
+            }
+        }
+        self.advance();
+
+        Ok(ast::Class {
+            name,
+            parent,
+            features,
+            span: self.span_from(start)
+        })
+    }
+
+    fn feature(&mut self) -> Parse<ast::Feature> {
+        let start = self.position;
+        let name = self.expect_identifier()?;
+        match *self.peek() {
+            TokenKind::LeftParen => {
+                self.advance();
+                let params = self.formals()?;
+                self.expect(TokenKind::Colon)?;
+                let return_type = self.expect_cool_type()?;
+                self.expect(TokenKind::LeftBrace)?;
+                let body = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::RightBrace)?;
+                self.expect(TokenKind::Semicolon)?;
+                Ok(ast::Feature::Method {
+                    name,
+                    params,
+                    return_type,
+                    body: Box::new(body),
+                    span: self.span_from(start)
+                })
+            },
+            TokenKind::Colon => {
+                self.advance();
+                let cool_type = self.expect_cool_type()?;
+                let expr = self.init(&[TokenKind::Semicolon])?;
+                self.expect(TokenKind::Semicolon)?;
+                Ok(ast::Feature::Attribute {
+                    name,
+                    cool_type,
+                    expr: Box::new(expr),
+                    span: self.span_from(start)
+                })
+            },
+            _ => Err(self.fail(&["':'", "'('"]))
+        }
+    }
+
+    // The formal parameters of a method, after its opening parenthesis.
+    fn formals(&mut self) -> Parse<Vec<ast::Formal>> {
+        let mut formals = Vec::new();
+        if *self.peek() == TokenKind::RightParen {
+            self.advance();
+            return Ok(formals);
+        }
+        loop {
+            let start = self.position;
+            let name = match *self.peek() {
+                TokenKind::Identifier(ref name) => *name,
+                _ if formals.is_empty() => return Err(self.fail(&[IDENTIFIER, "')'"])),
+                _ => return Err(self.fail(&[IDENTIFIER]))
+            };
+            self.advance();
+            self.expect(TokenKind::Colon)?;
+            let cool_type = self.expect_cool_type()?;
+            formals.push(ast::Formal {
+                name,
+                cool_type,
+                span: self.span_from(start)
+            });
+            match *self.peek() {
+                TokenKind::Comma => self.advance(),
+                TokenKind::RightParen => {
+                    self.advance();
+                    return Ok(formals);
+                },
+                _ => return Err(self.fail(&["','", "')'"]))
+            }
+        }
+    }
+
+    // An optional `<- expr` initializer for an attribute or a let binding,
+    // which must be followed by one of `follow`.
+    fn init(&mut self, follow: &[TokenKind]) -> Parse<ast::Expression> {
+        let mut expected: Vec<String> = follow.iter().map(|kind| kind.to_string()).collect();
+        let init = if *self.peek() == TokenKind::Assign {
+            self.advance();
+            expected.push(OPERATOR.to_string());
+            self.expression(ASSIGN)?
+        } else {
+            expected.push(TokenKind::Assign.to_string());
+            ast::Expression::new(ast::ExpressionKind::NoExpr, self.span_from(self.position))
+        };
+        if !follow.contains(self.peek()) {
+            let expected: Vec<&str> = expected.iter().map(|description| &description[..]).collect();
+            return Err(self.fail(&expected));
+        }
+        Ok(init)
+    }
+
+    // Parses an expression whose operators all bind at least as tightly
+    // as `precedence`.
+    fn expression(&mut self, precedence: u8) -> Parse<ast::Expression> {
+        let start = self.position;
+        let mut left = self.prefix()?;
+        // Whether `left` is a comparison made in this loop, which another
+        // comparison can't follow.
+        let mut compared = false;
+        loop {
+            let operator = self.peek();
+            let operator_precedence = match infix_precedence(operator) {
+                Some(operator_precedence) if operator_precedence >= precedence => operator_precedence,
+                _ => return Ok(left)
+            };
+            if operator_precedence == COMPARISON && compared {
+                let message = format!(
+                    "comparison operators do not associate, so {} cannot follow a comparison",
+                    operator);
+                return Err(self.fail_with(message));
+            }
+            self.advance();
+
+            let kind = match *operator {
+                TokenKind::Dot => {
+                    let (method, args) = self.call()?;
+                    ast::ExpressionKind::Dispatch(Box::new(left), method, args)
+                },
+                TokenKind::At => {
+                    let static_type = self.expect_cool_type()?;
+                    self.expect(TokenKind::Dot)?;
+                    let (method, args) = self.call()?;
+                    ast::ExpressionKind::StaticDispatch(Box::new(left), static_type, method, args)
+                },
+                _ => {
+                    let op = match *operator {
+                        TokenKind::Plus => ast::BinOp::Plus,
+                        TokenKind::Minus => ast::BinOp::Minus,
+                        TokenKind::Times => ast::BinOp::Mult,
+                        TokenKind::Divide => ast::BinOp::Divide,
+                        TokenKind::LessThan => ast::BinOp::LessThan,
+                        TokenKind::LessThanEqual => ast::BinOp::LessThanEqual,
+                        TokenKind::Equal => ast::BinOp::Equal,
+                        _ => unreachable!("{} is not a binary operator", operator)
+                    };
+                    let right = self.expression(operator_precedence + 1)?;
+                    ast::ExpressionKind::BinaryOperation(op, Box::new(left), Box::new(right))
+                }
+            };
+            compared = operator_precedence == COMPARISON;
+            left = ast::Expression::new(kind, self.span_from(start));
+        }
+    }
+
+    // The method name and arguments of a dispatch.
+    fn call(&mut self) -> Parse<(ast::Symbol, Vec<ast::Expression>)> {
+        let method = self.expect_identifier()?;
+        self.expect(TokenKind::LeftParen)?;
+        let args = self.args()?;
+        Ok((method, args))
+    }
+
+    // The arguments of a dispatch, after its opening parenthesis.
+    fn args(&mut self) -> Parse<Vec<ast::Expression>> {
+        let mut args = Vec::new();
+        if *self.peek() == TokenKind::RightParen {
+            self.advance();
+            return Ok(args);
+        }
+        if !starts_expression(self.peek()) {
+            return Err(self.fail(&["')'", EXPRESSION]));
+        }
+        loop {
+            args.push(self.expression(ASSIGN)?);
+            match *self.peek() {
+                TokenKind::Comma => self.advance(),
+                TokenKind::RightParen => {
+                    self.advance();
+                    return Ok(args);
+                },
+                _ => return Err(self.fail(&["','", "')'", OPERATOR]))
+            }
+        }
+    }
+
+    // Everything that can start an expression, up to the first binary or
+    // postfix operator.
+    fn prefix(&mut self) -> Parse<ast::Expression> {
+        let start = self.position;
+        let kind = match *self.peek() {
+            TokenKind::Identifier(ref name) => {
+                self.advance();
+                match *self.peek() {
+                    TokenKind::Assign => {
+                        self.advance();
+                        let value = self.expression(ASSIGN)?;
+                        ast::ExpressionKind::Assign(*name, Box::new(value))
+                    },
+                    TokenKind::LeftParen => {
+                        self.advance();
+                        let args = self.args()?;
+                        let span = self.span_from(start);
+                        let object = ast::Expression::new(ast::ExpressionKind::Identifier(symbol::SELF), span);
+                        ast::ExpressionKind::Dispatch(Box::new(object), *name, args)
+                    },
+                    _ => ast::ExpressionKind::Identifier(*name)
+                }
+            },
+            TokenKind::IntegerLiteral(digits) => {
+                self.advance();
+                ast::ExpressionKind::IntLiteral(digits)
+            },
+            TokenKind::StringLiteral(string) => {
+                self.advance();
+                ast::ExpressionKind::StringLiteral(string)
+            },
+            TokenKind::True => {
+                self.advance();
+                ast::ExpressionKind::True
+            },
+            TokenKind::False => {
+                self.advance();
+                ast::ExpressionKind::False
+            },
+            TokenKind::If => {
+                self.advance();
+                let condition = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::Then)?;
+                let then_branch = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::Else)?;
+                let else_branch = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::Fi)?;
+                ast::ExpressionKind::If(Box::new(condition), Box::new(then_branch), Box::new(else_branch))
+            },
+            TokenKind::While => {
+                self.advance();
+                let condition = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::Loop)?;
+                let body = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::Pool)?;
+                ast::ExpressionKind::While(Box::new(condition), Box::new(body))
+            },
+            TokenKind::LeftBrace => {
+                self.advance();
+                ast::ExpressionKind::Block(self.block()?)
+            },
+            TokenKind::LeftParen => {
+                // The parentheses aren't part of the expression's span.
+                self.advance();
+                let inner = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::RightParen)?;
+                return Ok(inner);
+            },
+            TokenKind::Let => return self.let_expression(),
+            TokenKind::Case => {
+                self.advance();
+                let value = self.expression(ASSIGN)?;
+                self.expect_after_expression(TokenKind::Of)?;
+                ast::ExpressionKind::Case(Box::new(value), self.case_branches()?)
+            },
+            TokenKind::New => {
+                self.advance();
+                ast::ExpressionKind::New(self.expect_cool_type()?)
+            },
+            TokenKind::Isvoid => {
+                self.advance();
+                ast::ExpressionKind::IsVoid(Box::new(self.expression(ISVOID + 1)?))
+            },
+            TokenKind::Tilde => {
+                self.advance();
+                ast::ExpressionKind::Negation(Box::new(self.expression(NEGATION + 1)?))
+            },
+            TokenKind::Not => {
+                self.advance();
+                ast::ExpressionKind::Not(Box::new(self.expression(NOT + 1)?))
+            },
+            _ => return Err(self.fail(&[EXPRESSION]))
+        };
+        Ok(ast::Expression::new(kind, self.span_from(start)))
+    }
+
+    // The statements of a block, after its opening brace. A statement we
+    // can't parse becomes an empty expression.
+    fn block(&mut self) -> Parse<Vec<ast::Expression>> {
+        let mut statements = Vec::new();
+        loop {
+            let start = self.position;
+            if !statements.is_empty() && *self.peek() == TokenKind::RightBrace {
+                self.advance();
+                return Ok(statements);
+            }
+            let statement = if statements.is_empty() || starts_expression(self.peek()) {
+                self.expression(ASSIGN)
+                    .and_then(|statement| self.expect_after_expression(TokenKind::Semicolon).map(|_| statement))
+            } else {
+                Err(self.fail(&["'}'", EXPRESSION]))
+            };
+            match statement {
+                Ok(statement) => statements.push(statement),
+                Err(failed) => {
+                    if !self.skip_to(start, &[TokenKind::Semicolon]) {
+                        return Err(failed);
+                    }
+                    self.advance();
+                    statements.push(ast::Expression::new(ast::ExpressionKind::NoExpr, self.span_from(start)));
+                }
+            }
+        }
+    }
+
+    // A let with one or more bindings, each of which becomes a let nested
+    // inside the one before it. The body extends as far as it can.
+    fn let_expression(&mut self) -> Parse<ast::Expression> {
+        // Where each binding's let starts: the `let` for the first and the
+        // comma before it for the rest
+        let mut bindings = Vec::new();
+        loop {
+            let start = self.position;
+            self.advance();
+            bindings.push((start, self.let_binding()?));
+            match *self.peek() {
+                TokenKind::Comma => {},
+                TokenKind::In => break,
+                _ => unreachable!("let_binding stops at 'in' or ','")
+            }
+        }
+        self.advance();
+
+        let mut body = self.expression(ASSIGN)?;
+        for (start, (name, cool_type, init)) in bindings.into_iter().rev() {
+            let kind = ast::ExpressionKind::Let(name, cool_type, Box::new(init), Box::new(body));
+            body = ast::Expression::new(kind, self.span_from(start));
+        }
+        Ok(body)
+    }
+
+    // One binding of a let, leaving the `in` or `,` after it. A binding we
+    // can't parse gets placeholder names.
+    fn let_binding(&mut self) -> Parse<(ast::Symbol, ast::CoolType, ast::Expression)> {
+        let start = self.position;
+        let binding = self.expect_identifier().and_then(|name| {
+            self.expect(TokenKind::Colon)?;
+            let cool_type = self.expect_cool_type()?;
+            let init = self.init(&[TokenKind::In, TokenKind::Comma])?;
+            Ok((name, cool_type, init))
+        });
+        binding.or_else(|failed| {
+            if !self.skip_to(start, &[TokenKind::In, TokenKind::Comma]) {
+                return Err(failed);
+            }
+            let init = ast::Expression::new(ast::ExpressionKind::NoExpr, self.span_from(start));
+            Ok((Symbol::id(ERROR_IDENTIFIER), ast::CoolType::new(symbol::NO_TYPE), init))
+        })
+    }
+
+    // The branches of a case, after its `of`, and the `esac` that ends it.
+    fn case_branches(&mut self) -> Parse<Vec<ast::CaseBranch>> {
+        let mut branches = Vec::new();
+        loop {
+            let start = self.position;
+            let name = match *self.peek() {
+                TokenKind::Identifier(ref name) => *name,
+                TokenKind::Esac if !branches.is_empty() => {
+                    self.advance();
+                    return Ok(branches);
+                },
+                _ if branches.is_empty() => return Err(self.fail(&[IDENTIFIER])),
+                _ => return Err(self.fail(&[IDENTIFIER, "'esac'"]))
+            };
+            self.advance();
+            self.expect(TokenKind::Colon)?;
+            let cool_type = self.expect_cool_type()?;
+            self.expect(TokenKind::Arrow)?;
+            let expr = self.expression(ASSIGN)?;
+            self.expect_after_expression(TokenKind::Semicolon)?;
+            branches.push(ast::CaseBranch {
+                name,
+                cool_type,
+                expr: Box::new(expr),
+                span: self.span_from(start)
+            });
+        }
+    }
+}
+
+// Parses one file's tokens into the same tree as super::parse_cool_program.
+pub fn parse_cool_program(tokens: &[Token]) -> Result<ast::Program, ParseErrors> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        errors: Vec::new()
+    };
+    let classes = parser.program();
+
+    let span = tokens[0].span.to(tokens[tokens.len() - 1].span);
+    let program = ast::Program { classes, span };
+    if parser.errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(ParseErrors { program, errors: parser.errors })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+    use parser;
+    use source::FileId;
+    use std::fs;
+
+    fn tokens(text: &str) -> Vec<Token> {
+        lexer::lex(text.chars().peekable(), FileId(0)).ok().unwrap()
+    }
+
+    // Parses `text` with both parsers, checking that they agree on the
+    // tree and on the errors.
+    fn cross_check(text: &str) -> Result<ast::Program, ParseErrors> {
+        let tokens = tokens(text);
+        let lalr = parser::parse_cool_program(&tokens, false);
+        let descent = parse_cool_program(&tokens);
+        match (lalr, descent) {
+            (Ok(lalr), Ok(descent)) => {
+                assert_eq!(lalr, descent);
+                Ok(descent)
+            },
+            (Err(lalr), Err(descent)) => {
+                let messages = |failure: &ParseErrors| -> Vec<(Span, String)> {
+                    failure.errors.iter().map(|error| (error.span, error.message.clone())).collect()
+                };
+                assert_eq!(messages(&lalr), messages(&descent));
+                assert_eq!(lalr.program, descent.program);
+                Err(descent)
+            },
+            (Ok(_), Err(descent)) => panic!("only the recursive-descent parser failed: {}", descent.errors[0].message),
+            (Err(lalr), Ok(_)) => panic!("only the LALR parser failed: {}", lalr.errors[0].message)
+        }
+    }
+
+    #[test]
+    fn agrees_on_the_example_programs() {
+        let mut paths: Vec<_> = fs::read_dir("examples").unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            cross_check(&fs::read_to_string(&path).unwrap()).ok().unwrap();
+        }
+    }
+
+    #[test]
+    fn agrees_on_precedence() {
+        cross_check("
+            class A {
+                f(x : Int) : Object {{
+                    x <- y <- 1 + 2 * 3 - 4 / 5;
+                    not x < 1 + 2;
+                    ~x.f() + isvoid x * 2;
+                    isvoid ~x@A.f(1, 2).g();
+                    a + not b + c;
+                    a * let x : Int <- 1, y : Int in x + y;
+                    (1 + 2) * 3 = 9;
+                    x <- if a then b else c fi + (while d loop e pool).f();
+                    case x of a : Int => a + 1; b : String => b; esac;
+                    new A.f() <= 3;
+                    self(1);
+                }};
+            };
+        ").ok().unwrap();
+    }
+
+    #[test]
+    fn agrees_on_errors() {
+        let texts = [
+            "",
+            "class A inherits { };",
+            "class A { x : Int; } class B { };",
+            "class A { f(x : Int) : Int { x + }; g : Int; };",
+            "class A { f() : Int { { x; x + ; x * ; x; } }; };",
+            "class A { f() : Int { let a : Int <- x +, b : Int in b }; };",
+            "class A { g : Int <- 1 2; };",
+            "class A { f() : Bool { 1 < 2 < 3 }; };",
+            "class A { f() : Bool { 1 = 2 + 3 <= 4 }; };",
+            "class A { f(x : Int, ) : Int { 1 }; };",
+            "class A { f() : Int { g(1,) }; };",
+            "class A { f() : Int { case x of esac }; };",
+            "class A { f() : Int { { } }; };",
+            "class A { 5 }; class B { };",
+            "class A { x : Int <- 1; ; y : Int; };",
+        ];
+        for text in texts.iter() {
+            cross_check(text).err().unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_non_associative_comparisons() {
+        let failure = parse_cool_program(&tokens("class A { f() : Bool { 1 < 2 = 3 }; };")).err().unwrap();
+        assert_eq!(failure.errors[0].message,
+                   "comparison operators do not associate, so '=' cannot follow a comparison");
+    }
+}
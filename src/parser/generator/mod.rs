@@ -0,0 +1,908 @@
+/*
+ * An LALR(1) parser generator. build.rs runs it over cool.grammar and
+ * writes out the tables and reduction actions that drive the parser.
+ *
+ * The grammar file has two sections separated by a line holding `%%`.
+ * The first declares terminals, precedence and the start symbol, one
+ * declaration per line:
+ *
+ *     %token NAME "description" PATTERN
+ *     %left NAME...        (also %right and %nonassoc, lowest first)
+ *     %group "description" NAME...
+ *     %start NAME
+ *
+ * PATTERN is the TokenKind pattern the terminal matches. Descriptions are
+ * used in error messages; a group stands in for all of its members when
+ * every one of them is expected. The terminal `error` is predefined.
+ *
+ * The second section holds the rules, yacc style:
+ *
+ *     lhs
+ *         : symbols... [%prec NAME] [{ rust code }]
+ *         | ...
+ *         ;
+ *
+ * The code runs when the rule is reduced, with `p` bound to the parser's
+ * value stacks, `span` to the source the rule covers and `tokens` to the
+ * tokens it matched.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symbol {
+    Terminal(usize),
+    Nonterminal(usize)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+    Nonassoc
+}
+
+pub struct Terminal {
+    pub name: String,
+    pub description: String,
+    // None for `error`, which never comes from the lexer
+    pub pattern: Option<String>
+}
+
+pub struct Rule {
+    pub lhs: usize,
+    pub rhs: Vec<Symbol>,
+    pub precedence: Option<(usize, Assoc)>,
+    pub action: String
+}
+
+pub struct Grammar {
+    pub terminals: Vec<Terminal>,
+    pub nonterminals: Vec<String>,
+    // Rule 0 is the added `$start -> start` rule, which accepts.
+    pub rules: Vec<Rule>,
+    pub groups: Vec<(String, Vec<usize>)>,
+    precedence: HashMap<String, (usize, Assoc)>,
+    eof: usize,
+    error: usize
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Error,
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+    // An error caused by a non-associative operator following itself
+    Nonassoc
+}
+
+pub struct Tables {
+    pub actions: Vec<Vec<Action>>,
+    // gotos[state][nonterminal], None where there is no transition
+    pub gotos: Vec<Vec<Option<usize>>>
+}
+
+const EOF_NAME: &str = "EOF";
+const ERROR_NAME: &str = "error";
+
+impl Grammar {
+    fn symbol_name(&self, symbol: Symbol) -> &str {
+        match symbol {
+            Symbol::Terminal(t) => &self.terminals[t].name,
+            Symbol::Nonterminal(n) => &self.nonterminals[n]
+        }
+    }
+
+    pub fn rule_text(&self, rule: usize) -> String {
+        let rule = &self.rules[rule];
+        let mut text = format!("{} ->", self.nonterminals[rule.lhs]);
+        for &symbol in &rule.rhs {
+            text.push(' ');
+            text.push_str(self.symbol_name(symbol));
+        }
+        text
+    }
+
+    fn item_text(&self, rule: usize, dot: usize) -> String {
+        let rule = &self.rules[rule];
+        let mut text = format!("{} ->", self.nonterminals[rule.lhs]);
+        for (i, &symbol) in rule.rhs.iter().enumerate() {
+            if i == dot {
+                text.push_str(" .");
+            }
+            text.push(' ');
+            text.push_str(self.symbol_name(symbol));
+        }
+        if dot == rule.rhs.len() {
+            text.push_str(" .");
+        }
+        text
+    }
+}
+
+// Splits a declaration line into words, keeping "quoted strings" whole
+// (without their quotes) and dropping any # comment.
+fn declaration_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+        } else if ch == '#' {
+            break;
+        } else if ch == '"' {
+            chars.next();
+            let mut word = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(ch) => word.push(ch),
+                    None => return Err("unterminated string".to_string())
+                }
+            }
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_whitespace() {
+                    break;
+                }
+                word.push(ch);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    Ok(words)
+}
+
+#[derive(Debug, PartialEq)]
+enum RuleToken {
+    Word(String),
+    Action(String),
+    Colon,
+    Bar,
+    Semicolon
+}
+
+// Splits the rules section into tokens. Actions run from a `{` to its
+// matching `}`; braces inside Rust string literals are skipped over.
+fn rule_tokens(text: &str, first_line: usize) -> Result<Vec<(RuleToken, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut line = first_line;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => line += 1,
+            '#' => {
+                while let Some(&ch) = chars.peek() {
+                    if ch == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            },
+            ':' => tokens.push((RuleToken::Colon, line)),
+            '|' => tokens.push((RuleToken::Bar, line)),
+            ';' => tokens.push((RuleToken::Semicolon, line)),
+            '{' => {
+                let start_line = line;
+                let mut code = String::new();
+                let mut depth = 1;
+                let mut in_string = false;
+                loop {
+                    let ch = match chars.next() {
+                        Some(ch) => ch,
+                        None => return Err(format!("line {}: unterminated action", start_line))
+                    };
+                    if ch == '\n' {
+                        line += 1;
+                    }
+                    if in_string {
+                        if ch == '\\' {
+                            code.push(ch);
+                            if let Some(escaped) = chars.next() {
+                                code.push(escaped);
+                            }
+                            continue;
+                        }
+                        in_string = ch != '"';
+                    } else if ch == '"' {
+                        in_string = true;
+                    } else if ch == '{' {
+                        depth += 1;
+                    } else if ch == '}' {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    code.push(ch);
+                }
+                tokens.push((RuleToken::Action(code), start_line));
+            },
+            '\'' => {
+                let mut word = String::from("'");
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\n') | None => return Err(format!("line {}: unterminated quoted symbol", line)),
+                        Some(ch) => word.push(ch)
+                    }
+                }
+                word.push('\'');
+                tokens.push((RuleToken::Word(word), line));
+            },
+            ch if ch.is_whitespace() => {},
+            ch if ch.is_alphanumeric() || ch == '_' || ch == '%' => {
+                let mut word = ch.to_string();
+                while let Some(&ch) = chars.peek() {
+                    if !(ch.is_alphanumeric() || ch == '_') {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push((RuleToken::Word(word), line));
+            },
+            ch => return Err(format!("line {}: unexpected '{}'", line, ch))
+        }
+    }
+    Ok(tokens)
+}
+
+pub fn parse_grammar(text: &str) -> Result<Grammar, String> {
+    let mut terminals = vec![Terminal {
+        name: ERROR_NAME.to_string(),
+        description: "an error".to_string(),
+        pattern: None
+    }];
+    let mut precedence = HashMap::new();
+    let mut group_names = Vec::new();
+    let mut start = None;
+
+    let mut lines = text.lines().enumerate();
+    let mut rules_line = None;
+    for (number, line) in &mut lines {
+        let number = number + 1;
+        if line.trim() == "%%" {
+            rules_line = Some(number + 1);
+            break;
+        }
+        let words = declaration_words(line).map_err(|err| format!("line {}: {}", number, err))?;
+        let (directive, args) = match words.split_first() {
+            Some((directive, args)) => (directive, args),
+            None => continue
+        };
+        match directive.as_ref() {
+            "%token" => {
+                if args.len() != 3 {
+                    return Err(format!("line {}: expected %token NAME \"description\" PATTERN", number));
+                }
+                if terminals.iter().any(|t: &Terminal| t.name == args[0]) {
+                    return Err(format!("line {}: terminal {} is declared twice", number, args[0]));
+                }
+                terminals.push(Terminal {
+                    name: args[0].clone(),
+                    description: args[1].clone(),
+                    pattern: Some(args[2].clone())
+                });
+            },
+            "%left" | "%right" | "%nonassoc" => {
+                let assoc = match directive.as_ref() {
+                    "%left" => Assoc::Left,
+                    "%right" => Assoc::Right,
+                    _ => Assoc::Nonassoc
+                };
+                let level = precedence.len();
+                for name in args {
+                    precedence.insert(name.clone(), (level, assoc));
+                }
+            },
+            "%group" => {
+                match args.split_first() {
+                    Some((description, members)) if !members.is_empty() => {
+                        group_names.push((number, description.clone(), members.to_vec()));
+                    },
+                    _ => return Err(format!("line {}: expected %group \"description\" NAME...", number))
+                }
+            },
+            "%start" => {
+                if args.len() != 1 {
+                    return Err(format!("line {}: expected %start NAME", number));
+                }
+                start = Some(args[0].clone());
+            },
+            _ => return Err(format!("line {}: unknown declaration {}", number, directive))
+        }
+    }
+
+    let rules_line = rules_line.ok_or_else(|| "missing %% before the rules".to_string())?;
+    let start = start.ok_or_else(|| "missing %start".to_string())?;
+    let eof = terminals.iter().position(|t| t.name == EOF_NAME)
+        .ok_or_else(|| format!("missing %token {}", EOF_NAME))?;
+
+    let terminal_index = |name: &str, terminals: &[Terminal]| terminals.iter().position(|t| t.name == name);
+    let mut groups = Vec::new();
+    for (number, description, members) in group_names {
+        let mut indices = Vec::new();
+        for member in members {
+            match terminal_index(&member, &terminals) {
+                Some(index) => indices.push(index),
+                None => return Err(format!("line {}: {} is not a terminal", number, member))
+            }
+        }
+        groups.push((description, indices));
+    }
+
+    let rest: Vec<&str> = lines.map(|(_, line)| line).collect();
+    let tokens = rule_tokens(&rest.join("\n"), rules_line)?;
+
+    // The left hand sides are the nonterminals; everything else used in a
+    // rule must be a declared terminal.
+    let mut nonterminals = vec!["$start".to_string()];
+    for window in tokens.windows(2) {
+        if let (&(RuleToken::Word(ref name), _), &(RuleToken::Colon, _)) = (&window[0], &window[1]) {
+            if !nonterminals.contains(name) {
+                nonterminals.push(name.clone());
+            }
+        }
+    }
+    let start_index = nonterminals.iter().position(|n| *n == start)
+        .ok_or_else(|| format!("start symbol {} has no rules", start))?;
+
+    let mut rules = vec![Rule {
+        lhs: 0,
+        rhs: vec![Symbol::Nonterminal(start_index)],
+        precedence: None,
+        action: String::new()
+    }];
+    let mut tokens = tokens.into_iter();
+    while let Some((token, line)) = tokens.next() {
+        let lhs = match token {
+            RuleToken::Word(ref name) => nonterminals.iter().position(|n| n == name),
+            _ => None
+        }.ok_or_else(|| format!("line {}: expected the name of a nonterminal", line))?;
+        match tokens.next() {
+            Some((RuleToken::Colon, _)) => {},
+            _ => return Err(format!("line {}: expected ':'", line))
+        }
+        // One alternative each time around, until the `;` that ends them.
+        loop {
+            let mut rhs = Vec::new();
+            let mut explicit_precedence = None;
+            let mut action = String::new();
+            let more = loop {
+                match tokens.next() {
+                    Some((RuleToken::Word(ref word), line)) if word == "%prec" => {
+                        match tokens.next() {
+                            Some((RuleToken::Word(ref name), _)) if precedence.contains_key(name) => {
+                                explicit_precedence = Some(precedence[name]);
+                            },
+                            _ => return Err(format!("line {}: %prec needs a name with a precedence", line))
+                        }
+                    },
+                    Some((RuleToken::Word(word), line)) => {
+                        if !action.is_empty() {
+                            return Err(format!("line {}: symbols after an action", line));
+                        }
+                        if let Some(n) = nonterminals.iter().position(|n| *n == word) {
+                            rhs.push(Symbol::Nonterminal(n));
+                        } else if let Some(t) = terminal_index(&word, &terminals) {
+                            rhs.push(Symbol::Terminal(t));
+                        } else {
+                            return Err(format!("line {}: {} is neither a terminal nor a nonterminal", line, word));
+                        }
+                    },
+                    Some((RuleToken::Action(code), _)) => action = code,
+                    Some((RuleToken::Bar, _)) => break true,
+                    Some((RuleToken::Semicolon, _)) | None => break false,
+                    Some((RuleToken::Colon, line)) => return Err(format!("line {}: missing ';' before this rule", line))
+                }
+            };
+
+            // The last terminal of a rule gives it its precedence unless
+            // it has a %prec.
+            let implicit_precedence = rhs.iter().rev().filter_map(|&symbol| {
+                match symbol {
+                    Symbol::Terminal(t) => Some(t),
+                    Symbol::Nonterminal(_) => None
+                }
+            }).next().and_then(|t| precedence.get(&terminals[t].name).cloned());
+            rules.push(Rule {
+                lhs,
+                rhs,
+                precedence: explicit_precedence.or(implicit_precedence),
+                action
+            });
+            if !more {
+                break;
+            }
+        }
+    }
+
+    Ok(Grammar {
+        terminals,
+        nonterminals,
+        rules,
+        groups,
+        precedence,
+        eof,
+        error: 0
+    })
+}
+
+// An LR(0) item: a rule and how much of it has been matched.
+type Item = (usize, usize);
+
+struct Analysis<'a> {
+    grammar: &'a Grammar,
+    nullable: Vec<bool>,
+    first: Vec<BTreeSet<usize>>,
+    rules_for: Vec<Vec<usize>>
+}
+
+impl<'a> Analysis<'a> {
+    fn new(grammar: &'a Grammar) -> Analysis<'a> {
+        let count = grammar.nonterminals.len();
+        let mut rules_for = vec![Vec::new(); count];
+        for (index, rule) in grammar.rules.iter().enumerate() {
+            rules_for[rule.lhs].push(index);
+        }
+
+        let mut nullable = vec![false; count];
+        let mut first = vec![BTreeSet::new(); count];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &grammar.rules {
+                let mut all_nullable = true;
+                for &symbol in &rule.rhs {
+                    match symbol {
+                        Symbol::Terminal(t) => {
+                            changed |= first[rule.lhs].insert(t);
+                            all_nullable = false;
+                        },
+                        Symbol::Nonterminal(n) => {
+                            let new: Vec<usize> = first[n].iter().cloned().collect();
+                            for t in new {
+                                changed |= first[rule.lhs].insert(t);
+                            }
+                            all_nullable = nullable[n];
+                        }
+                    }
+                    if !all_nullable {
+                        break;
+                    }
+                }
+                if all_nullable && !nullable[rule.lhs] {
+                    nullable[rule.lhs] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        Analysis { grammar, nullable, first, rules_for }
+    }
+
+    // The terminals that can start `symbols`, and whether all of them
+    // can be empty.
+    fn first_of(&self, symbols: &[Symbol]) -> (BTreeSet<usize>, bool) {
+        let mut first = BTreeSet::new();
+        for &symbol in symbols {
+            match symbol {
+                Symbol::Terminal(t) => {
+                    first.insert(t);
+                    return (first, false);
+                },
+                Symbol::Nonterminal(n) => {
+                    first.extend(self.first[n].iter().cloned());
+                    if !self.nullable[n] {
+                        return (first, false);
+                    }
+                }
+            }
+        }
+        (first, true)
+    }
+
+    fn next_symbol(&self, (rule, dot): Item) -> Option<Symbol> {
+        self.grammar.rules[rule].rhs.get(dot).cloned()
+    }
+
+    fn closure(&self, kernel: &[Item]) -> Vec<Item> {
+        let mut items = kernel.to_vec();
+        let mut index = 0;
+        while index < items.len() {
+            if let Some(Symbol::Nonterminal(n)) = self.next_symbol(items[index]) {
+                for &rule in &self.rules_for[n] {
+                    if !items.contains(&(rule, 0)) {
+                        items.push((rule, 0));
+                    }
+                }
+            }
+            index += 1;
+        }
+        items
+    }
+
+    // The closure of a kernel with a lookahead set for every item.
+    fn closure_with_lookaheads(&self, kernel: &BTreeMap<Item, BTreeSet<usize>>) -> BTreeMap<Item, BTreeSet<usize>> {
+        let mut items = kernel.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let snapshot: Vec<(Item, BTreeSet<usize>)> = items.iter()
+                .map(|(&item, lookaheads)| (item, lookaheads.clone()))
+                .collect();
+            for ((rule, dot), lookaheads) in snapshot {
+                if let Some(Symbol::Nonterminal(n)) = self.next_symbol((rule, dot)) {
+                    let (mut follow, nullable) = self.first_of(&self.grammar.rules[rule].rhs[dot + 1 ..]);
+                    if nullable {
+                        follow.extend(lookaheads.iter().cloned());
+                    }
+                    for &child in &self.rules_for[n] {
+                        let entry = items.entry((child, 0)).or_default();
+                        for &t in &follow {
+                            changed |= entry.insert(t);
+                        }
+                    }
+                }
+            }
+        }
+        items
+    }
+}
+
+struct Automaton {
+    kernels: Vec<Vec<Item>>,
+    transitions: Vec<BTreeMap<Symbol, usize>>
+}
+
+fn lr0_automaton(analysis: &Analysis) -> Automaton {
+    let mut kernels = vec![vec![(0, 0)]];
+    let mut transitions = Vec::new();
+    let mut index_of: HashMap<Vec<Item>, usize> = HashMap::new();
+    index_of.insert(kernels[0].clone(), 0);
+
+    let mut state = 0;
+    while state < kernels.len() {
+        let mut moves: BTreeMap<Symbol, Vec<Item>> = BTreeMap::new();
+        for item in analysis.closure(&kernels[state]) {
+            if let Some(symbol) = analysis.next_symbol(item) {
+                moves.entry(symbol).or_default().push((item.0, item.1 + 1));
+            }
+        }
+        let mut targets = BTreeMap::new();
+        for (symbol, mut kernel) in moves {
+            kernel.sort();
+            let next = match index_of.get(&kernel) {
+                Some(&next) => next,
+                None => {
+                    kernels.push(kernel.clone());
+                    index_of.insert(kernel, kernels.len() - 1);
+                    kernels.len() - 1
+                }
+            };
+            targets.insert(symbol, next);
+        }
+        transitions.push(targets);
+        state += 1;
+    }
+
+    Automaton { kernels, transitions }
+}
+
+// Builds the LALR(1) tables. Conflicts that precedence declarations don't
+// settle are returned as descriptions, one per conflicting state and token.
+pub fn build_tables(grammar: &Grammar) -> Result<Tables, Vec<String>> {
+    let analysis = Analysis::new(grammar);
+    let automaton = lr0_automaton(&analysis);
+    let state_count = automaton.kernels.len();
+
+    // Propagate lookaheads from each state's kernel items through its
+    // closure and across its transitions until nothing changes.
+    let mut kernel_lookaheads: Vec<BTreeMap<Item, BTreeSet<usize>>> = automaton.kernels.iter()
+        .map(|kernel| kernel.iter().map(|&item| (item, BTreeSet::new())).collect())
+        .collect();
+    kernel_lookaheads[0].get_mut(&(0, 0)).unwrap().insert(grammar.eof);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for state in 0 .. state_count {
+            let items = analysis.closure_with_lookaheads(&kernel_lookaheads[state]);
+            for ((rule, dot), lookaheads) in items {
+                if let Some(symbol) = analysis.next_symbol((rule, dot)) {
+                    let target = automaton.transitions[state][&symbol];
+                    let entry = kernel_lookaheads[target].get_mut(&(rule, dot + 1)).unwrap();
+                    for t in lookaheads {
+                        changed |= entry.insert(t);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut actions = vec![vec![Action::Error; grammar.terminals.len()]; state_count];
+    let mut gotos = vec![vec![None; grammar.nonterminals.len()]; state_count];
+    let mut conflicts = Vec::new();
+    for state in 0 .. state_count {
+        for (&symbol, &target) in &automaton.transitions[state] {
+            match symbol {
+                Symbol::Terminal(t) => actions[state][t] = Action::Shift(target),
+                Symbol::Nonterminal(n) => gotos[state][n] = Some(target)
+            }
+        }
+
+        let items = analysis.closure_with_lookaheads(&kernel_lookaheads[state]);
+        for (&(rule, dot), lookaheads) in &items {
+            if dot < grammar.rules[rule].rhs.len() {
+                continue;
+            }
+            for &t in lookaheads {
+                let reduce = if rule == 0 { Action::Accept } else { Action::Reduce(rule) };
+                let resolved = match actions[state][t] {
+                    Action::Error => Some(reduce),
+                    Action::Shift(_) => resolve_shift_reduce(grammar, rule, t, actions[state][t]),
+                    _ => None
+                };
+                match resolved {
+                    Some(action) => actions[state][t] = action,
+                    None => {
+                        let description = match actions[state][t] {
+                            Action::Shift(_) => "shift/reduce",
+                            _ => "reduce/reduce"
+                        };
+                        let mut message = format!("state {}: {} conflict on {}", state, description, grammar.terminals[t].name);
+                        for &(rule, dot) in items.keys() {
+                            let relevant = match analysis.next_symbol((rule, dot)) {
+                                Some(symbol) => symbol == Symbol::Terminal(t),
+                                None => items[&(rule, dot)].contains(&t)
+                            };
+                            if relevant {
+                                write!(message, "\n    {}", grammar.item_text(rule, dot)).unwrap();
+                            }
+                        }
+                        conflicts.push(message);
+                    }
+                }
+            }
+        }
+    }
+
+    // As in yacc, a state that can only reduce by one rule does so
+    // whatever comes next. A bad token is then reported by the state that
+    // was expecting it, rather than by one that had already finished a
+    // construct and so can't recover without throwing it away.
+    for row in &mut actions {
+        let mut only_reduce = None;
+        for &action in row.iter() {
+            match (action, only_reduce) {
+                (Action::Error, _) => {},
+                (Action::Reduce(rule), None) => only_reduce = Some(rule),
+                (Action::Reduce(rule), Some(other)) if rule == other => {},
+                _ => {
+                    only_reduce = None;
+                    break;
+                }
+            }
+        }
+        if let Some(rule) = only_reduce {
+            for (terminal, action) in row.iter_mut().enumerate() {
+                if terminal != grammar.error {
+                    *action = Action::Reduce(rule);
+                }
+            }
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(Tables { actions, gotos })
+    } else {
+        Err(conflicts)
+    }
+}
+
+// Settles a shift/reduce conflict the way yacc does, by comparing the
+// precedence of the rule with that of the lookahead. None if either has
+// no precedence.
+fn resolve_shift_reduce(grammar: &Grammar, rule: usize, terminal: usize, shift: Action) -> Option<Action> {
+    let (rule_level, _) = grammar.rules[rule].precedence?;
+    let &(token_level, assoc) = grammar.precedence.get(&grammar.terminals[terminal].name)?;
+    Some(if rule_level > token_level {
+        Action::Reduce(rule)
+    } else if rule_level < token_level {
+        shift
+    } else {
+        match assoc {
+            Assoc::Left => Action::Reduce(rule),
+            Assoc::Right => shift,
+            Assoc::Nonassoc => Action::Nonassoc
+        }
+    })
+}
+
+fn action_text(action: Action) -> String {
+    match action {
+        Action::Error => "E".to_string(),
+        Action::Shift(state) => format!("S({})", state),
+        Action::Reduce(rule) => format!("R({})", rule),
+        Action::Accept => "A".to_string(),
+        Action::Nonassoc => "N".to_string()
+    }
+}
+
+// Writes the tables out as Rust, to be included into the parser module,
+// which defines the Action enum. The action table uses short names for
+// its variants to keep it readable.
+pub fn write_parser(grammar: &Grammar, tables: &Tables) -> String {
+    let mut out = String::new();
+    let terminal_count = grammar.terminals.len();
+    let nonterminal_count = grammar.nonterminals.len();
+    let state_count = tables.actions.len();
+    let rule_count = grammar.rules.len();
+
+    writeln!(out, "// Generated by build.rs from src/parser/cool.grammar; do not edit.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "const ERROR_TERMINAL: usize = {};", grammar.error).unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "static TERMINAL_DESCRIPTIONS: [&str; {}] = [", terminal_count).unwrap();
+    for terminal in &grammar.terminals {
+        writeln!(out, "    {:?},", terminal.description).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "static GROUPS: [(&str, &[usize]); {}] = [", grammar.groups.len()).unwrap();
+    for group in &grammar.groups {
+        writeln!(out, "    ({:?}, &{:?}),", group.0, group.1).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "fn terminal(kind: &TokenKind) -> usize {{").unwrap();
+    writeln!(out, "    match *kind {{").unwrap();
+    for (index, terminal) in grammar.terminals.iter().enumerate() {
+        if let Some(ref pattern) = terminal.pattern {
+            writeln!(out, "        {} => {},", pattern, index).unwrap();
+        }
+    }
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "// (left hand side, length) of each rule").unwrap();
+    writeln!(out, "static RULES: [(usize, usize); {}] = [", rule_count).unwrap();
+    for rule in &grammar.rules {
+        writeln!(out, "    ({}, {}),", rule.lhs, rule.rhs.len()).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "static RULE_TEXT: [&str; {}] = [", rule_count).unwrap();
+    for rule in 0 .. rule_count {
+        writeln!(out, "    {:?},", grammar.rule_text(rule)).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "use self::Action::Shift as S;").unwrap();
+    writeln!(out, "use self::Action::Reduce as R;").unwrap();
+    writeln!(out, "const E: Action = Action::Error;").unwrap();
+    writeln!(out, "const A: Action = Action::Accept;").unwrap();
+    writeln!(out, "const N: Action = Action::Nonassoc;").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "static ACTIONS: [[Action; {}]; {}] = [", terminal_count, state_count).unwrap();
+    for row in &tables.actions {
+        let entries: Vec<String> = row.iter().map(|&action| action_text(action)).collect();
+        writeln!(out, "    [{}],", entries.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "static GOTOS: [[Option<usize>; {}]; {}] = [", nonterminal_count, state_count).unwrap();
+    for row in &tables.gotos {
+        writeln!(out, "    {:?},", row).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "fn reduce_action(p: &mut Values, rule: usize, span: Span, tokens: &[Token]) {{").unwrap();
+    writeln!(out, "    match rule {{").unwrap();
+    for (index, rule) in grammar.rules.iter().enumerate() {
+        if rule.action.trim().is_empty() {
+            continue;
+        }
+        writeln!(out, "        // {}", grammar.rule_text(index)).unwrap();
+        writeln!(out, "        {} => {{{}}},", index, rule.action).unwrap();
+    }
+    writeln!(out, "        _ => {{}}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "
+        %token EOF \"end of file\" TokenKind::Eof
+        %token NUM \"a number\" TokenKind::IntegerLiteral(_)
+        %token '+' \"'+'\" TokenKind::Plus
+        %token '*' \"'*'\" TokenKind::Times
+        %token '<' \"'<'\" TokenKind::LessThan
+    ";
+
+    fn tables(declarations: &str, rules: &str) -> Result<Tables, Vec<String>> {
+        let text = format!("{}{}\n%start e\n%%\n{}", HEADER, declarations, rules);
+        let grammar = parse_grammar(&text).unwrap();
+        build_tables(&grammar)
+    }
+
+    #[test]
+    fn reports_ambiguity() {
+        let conflicts = tables("", "e : e '+' e | NUM ;").err().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].contains("shift/reduce conflict on '+'"));
+        assert!(conflicts[0].contains("e -> e '+' e ."));
+        assert!(conflicts[0].contains("e -> e . '+' e"));
+    }
+
+    #[test]
+    fn reports_reduce_reduce_conflicts() {
+        let conflicts = tables("", "e : a | b ; a : NUM ; b : NUM ;").err().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].contains("reduce/reduce conflict on EOF"));
+    }
+
+    #[test]
+    fn precedence_settles_conflicts() {
+        let tables = tables("
+            %nonassoc '<'
+            %left '+'
+            %left '*'
+        ", "e : e '+' e | e '*' e | e '<' e | NUM ;").ok().unwrap();
+        let shift = |state: usize, terminal: usize| match tables.actions[state][terminal] {
+            Action::Shift(next) => next,
+            action => panic!("expected a shift but found {:?}", action)
+        };
+        // The states reached after `e + e` and `e < e`
+        let e = tables.gotos[0][1].unwrap();
+        let plus = tables.gotos[shift(e, 3)][1].unwrap();
+        let less = tables.gotos[shift(e, 5)][1].unwrap();
+
+        assert_eq!(tables.actions[plus][3], Action::Reduce(1));
+        shift(plus, 4);
+        assert_eq!(tables.actions[less][5], Action::Nonassoc);
+        assert_eq!(tables.actions[less][3], Action::Shift(shift(e, 3)));
+    }
+
+    #[test]
+    fn reduces_by_default_in_states_that_only_reduce() {
+        let tables = tables("", "e : NUM | e '+' NUM ;").ok().unwrap();
+        let num = match tables.actions[0][2] {
+            Action::Shift(state) => state,
+            action => panic!("expected a shift but found {:?}", action)
+        };
+        assert_eq!(tables.actions[num][4], Action::Reduce(1));
+        assert_eq!(tables.actions[num][0], Action::Error);
+        // Not where there's a shift to choose instead
+        let e = tables.gotos[0][1].unwrap();
+        assert_eq!(tables.actions[e][4], Action::Error);
+    }
+
+    #[test]
+    fn writes_actions_into_the_reducer() {
+        let text = format!("{}%start e\n%%\ne : NUM {{ p.push(tokens[0]) }} ;", HEADER);
+        let grammar = parse_grammar(&text).unwrap();
+        let tables = build_tables(&grammar).ok().unwrap();
+        let source = write_parser(&grammar, &tables);
+        assert!(source.contains("// e -> NUM\n        1 => { p.push(tokens[0]) },"));
+        assert!(source.contains("TokenKind::IntegerLiteral(_) => 2,"));
+    }
+}
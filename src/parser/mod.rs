@@ -1,1686 +1,756 @@
+/*
+ * A table-driven LALR(1) parser. The tables, and the actions that build
+ * the AST as rules are reduced, are generated from cool.grammar by
+ * build.rs; this module runs them and recovers from syntax errors.
+ */
+
 use ast;
 use lexer::Token;
-use std::rc::Rc;
+use lexer::TokenKind;
+use source::Span;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::mem;
+use symbol;
+use symbol::Symbol;
 
-// Used when there's only one possible token to expect and
-// we don't need to store its value anywhere
-macro_rules! match_single {
-    ($tok:expr, $expected:pat, $states:ident, $new_state:expr) => {{
-        match *$tok {
-            $expected => {
-                $states.push($new_state);
-            },
-            ref e @ _ => {
-                println!("Errored in state {}, got {:?}", $states.last().unwrap(), e);
-                return None;
-            }
-        }
-    }}
+pub mod descent;
+#[cfg(test)]
+mod generator;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Error,
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+    // A non-associative operator following itself, as in `a < b < c`
+    Nonassoc
 }
 
-// Used when the only thing we expect is a type.
-macro_rules! match_type {
-    ($tok:expr, $states:ident, $new_state:expr, $types:expr) => {{
-        match *$tok {
-            Token::Type(ref type_name) => {
-                $types.push(type_name.clone());
-                $states.push($new_state);
-            },
-            _ => {
-                println!("Errored in state {}, expected a type", $states.last().unwrap());
-                return None;
-            }
-        }
-    }}
+include!(concat!(env!("OUT_DIR"), "/cool_parser.rs"));
+
+pub struct ParseError {
+    pub span: Span,
+    pub message: String
 }
 
-// Used when the only thing we expect is an identifier.
-macro_rules! match_identifier {
-    ($tok:expr, $states:ident, $new_state:expr, $ids:expr) => {{
-        match *$tok {
-            Token::Identifier(ref id) => {
-                $ids.push(id.clone());
-                $states.push($new_state);
-            },
-            _ => {
-                println!("Errored in state {}, expected an identifier", $states.last().unwrap());
-                return None;
-            }
-        }
-    }}
+// Returned when a program has syntax errors. `program` holds everything
+// that could be salvaged around them.
+pub struct ParseErrors {
+    pub program: ast::Program,
+    pub errors: Vec<ParseError>
 }
 
-macro_rules! match_single_capture {
-    ($tok:expr, $expected:pat, $states:ident, $new_state:expr, $body:stmt) => {{
-        match *$tok {
-            $expected => {
-                $states.push($new_state);
-                $body
-            },
-            _ => {
-                println!("Errored in state {}", $states.last().unwrap());
-                return None;
-            }
-        }
-    }}
+// Lists alternatives the way they're written in English: "a", "a or b",
+// "a, b or c".
+fn one_of(alternatives: &[&str]) -> String {
+    match alternatives.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new()
+    }
 }
 
-// Pops states and then looks up what state we should go to next
-// in the goto table
-macro_rules! reduce {
-    ($states:expr, $rule_num:expr, $rule_len:expr) => {{
-        println!("Reducing using rule #{}", $rule_num);
-        for _ in 0 .. $rule_len {
-            $states.pop();
-        }
-        let curr = *$states.last().unwrap();
-        $states.push(goto(curr, $rule_num));
-    }}
+// The placeholder name for a let binding we couldn't parse, whose type
+// is symbol::NO_TYPE.
+const ERROR_IDENTIFIER: &str = "_error";
+
+// The values of the symbols on the state stack, one stack per kind of
+// value. The grammar's actions pop and push these.
+struct Values {
+    classes: Vec<ast::Class>,
+    types: Vec<ast::Symbol>,
+    features: Vec<ast::Feature>,
+    formals: Vec<ast::Formal>,
+    identifiers: Vec<ast::Symbol>,
+    expressions: Vec<ast::Expression>,
+    expression_lists: Vec<Vec<ast::Expression>>,
+    case_branches: Vec<Vec<ast::CaseBranch>>
 }
 
-// Matches the start of an expression
-macro_rules! expression_start {
-    ($tok:expr, $states:ident, $ids:ident, $exprs:ident, $expr_lists:ident) => {
-        match *$tok {
-            Token::Identifier(ref id) => {
-                $ids.push(id.clone());
-                $states.push(30);
-            },
-            Token::If => { $states.push(31); },
-            Token::While => { $states.push(32); },
-            Token::Let => { $states.push(33); },
-            Token::Case => { $states.push(34); },
-            Token::New => { $states.push(35); },
-            Token::Isvoid => { $states.push(36); },
-            Token::Not => { $states.push(37); },
-            Token::LeftBrace => {
-                $expr_lists.push(Vec::new());
-                $states.push(38);
-            },
-            Token::LeftParen => { $states.push(39); },
-            Token::Tilde => { $states.push(40); },
-            Token::StringLiteral(ref string) => {
-                $exprs.push(Box::new(ast::Expression::StringLiteral(string.clone())));
-                $states.push(42);
-            },
-            Token::True => {
-                $exprs.push(Box::new(ast::Expression::True));
-                $states.push(43);
-            },
-            Token::False => {
-                $exprs.push(Box::new(ast::Expression::False));
-                $states.push(44);
-            },
-            Token::IntegerLiteral(val) => {
-                $exprs.push(Box::new(ast::Expression::IntLiteral(val)));
-                $states.push(160);
-            },
-            ref e @ _ => {
-                println!("Errored in state {}, got {:?}", $states.last().unwrap(), e);
-                return None;
-            }
-        }
-    }
+// How big each of the value stacks was at some point, so that error
+// recovery can throw away the values of a half-parsed construct.
+#[derive(Clone, Copy)]
+struct ValueMarks {
+    classes: usize,
+    types: usize,
+    features: usize,
+    formals: usize,
+    identifiers: usize,
+    expressions: usize,
+    expression_lists: usize,
+    case_branches: usize
 }
 
-// Matches after reducing an expression
-macro_rules! after_expression {
-    ($tok:expr, $states:ident, $expected:pat, $new_state:expr, $types:ident, $no_type:ident, $use_token:ident, $isStatic:ident) => {{
-        match *$tok {
-            $expected => { $states.push($new_state); },
-            Token::Dot => {
-                $isStatic.push(false);
-                $states.push(101);
-                $use_token = false;
-            },
-            Token::At => {
-                $isStatic.push(true);
-                $states.push(46);
-            },
-            Token::Plus => { $states.push(47); },
-            Token::Minus => { $states.push(48); },
-            Token::Times => { $states.push(49); },
-            Token::Divide => { $states.push(50); },
-            Token::LessThan => { $states.push(51); },
-            Token::LessThanEqual => { $states.push(52); },
-            Token::Equal => { $states.push(53); },
-            ref e @ _ => {
-                println!("Errored in state {}, got {:?}", $states.last().unwrap(), e);
-                return None;
-            }
+impl Values {
+    fn new() -> Values {
+        Values {
+            classes: Vec::new(),
+            types: Vec::new(),
+            features: Vec::new(),
+            formals: Vec::new(),
+            identifiers: Vec::new(),
+            expressions: Vec::new(),
+            expression_lists: Vec::new(),
+            case_branches: Vec::new()
         }
-    }}
-}
+    }
 
-macro_rules! on_expression_goto {
-    ($state:ident, $rule:ident, $new_state:expr) => {{
-        match $rule {
-            15 ... 40 => {
-                $new_state
-            },
-            _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", $state, $rule)
+    fn marks(&self) -> ValueMarks {
+        ValueMarks {
+            classes: self.classes.len(),
+            types: self.types.len(),
+            features: self.features.len(),
+            formals: self.formals.len(),
+            identifiers: self.identifiers.len(),
+            expressions: self.expressions.len(),
+            expression_lists: self.expression_lists.len(),
+            case_branches: self.case_branches.len()
         }
-    }}
-}
+    }
+
+    fn truncate(&mut self, marks: ValueMarks) {
+        self.classes.truncate(marks.classes);
+        self.types.truncate(marks.types);
+        self.features.truncate(marks.features);
+        self.formals.truncate(marks.formals);
+        self.identifiers.truncate(marks.identifiers);
+        self.expressions.truncate(marks.expressions);
+        self.expression_lists.truncate(marks.expression_lists);
+        self.case_branches.truncate(marks.case_branches);
+    }
 
-macro_rules! after_static_goto {
-    ($state:ident, $rule:ident) => {{
-        match $rule {
-            45 => 45,
-            _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", $state, $rule)
+    // Type names and identifiers are pushed as they're shifted; every
+    // other token's value is read by the action that reduces it.
+    fn shift(&mut self, token: &Token) {
+        match token.kind {
+            TokenKind::Type(ref name) => self.types.push(*name),
+            TokenKind::Identifier(ref name) => self.identifiers.push(*name),
+            _ => {}
         }
-    }}
-}
+    }
 
-pub fn parse_cool_program(tokens: &Vec<Token>) -> Option<ast::Program> {
-    let mut class_lists: Vec<Box<ast::Class>> = Vec::new();
-    let mut last_class: Option<Box<ast::Class>> = None;
-    let mut types: Vec<ast::CoolType> = Vec::new();
-    let mut features: Vec<Box<ast::Feature>> = Vec::new();
-    let mut formals: Vec<ast::Formal> = Vec::new();
-    let mut identifiers: Vec<ast::Symbol> = Vec::new();
-    let mut expressions: Vec<Box<ast::Expression>> = Vec::new();
-    let mut expression_lists: Vec<Vec<Box<ast::Expression>>> = Vec::new();
-    let mut case_branches: Vec<Vec<Box<ast::CaseBranch>>> = Vec::new();
-    let mut states: Vec<i32> = vec![0];
-    let mut is_statics: Vec<bool> = Vec::new();
+    fn push(&mut self, kind: ast::ExpressionKind, span: Span) {
+        self.expressions.push(ast::Expression::new(kind, span));
+    }
 
-    // Constants
-    let object = Rc::new("Object".to_string());
-    let self_obj = Rc::new("self".to_string());
+    fn pop(&mut self) -> Box<ast::Expression> {
+        Box::new(self.expressions.pop().unwrap())
+    }
 
-    let mut tokens_iter = tokens.iter().peekable();
-    loop {
-        let mut should_consume = true;
-        if let Some(&curr) = tokens_iter.peek() {
-            println!("{:?}, next: {:?}", states, curr);
-            match *states.last().unwrap() {
-                0 => {
-                    match_single!(curr, Token::Class, states, 4);
-                },
-                1 => {
-                    match *curr {
-                        Token::Class => {
-                            states.push(4);
-                        },
-                        Token::EOF => {
-                            println!("Successfull parse!");
-                            break;
-                        },
-                        _ => {
-                            println!("Errored in state {}", states.last().unwrap());
-                            return None;
-                        }
-                    }
-                },
-                2 => {
-                    match_single!(curr, Token::Semicolon, states, 3);
-                },
-                3 => {
-                    class_lists.push(last_class.unwrap());
-                    last_class = None;
-                    // 0:      P -> P C ;
-                    reduce!(states, 0, 3);
-                    should_consume = false;
-                },
-                4 => {
-                    match_type!(curr, states, 5, types);
-                },
-                5 => {
-                    match *curr {
-                        Token::Inherits => {
-                            states.push(7);
-                        },
-                        Token::LeftBrace => {
-                            types.push(object.clone());
-                            states.push(6);
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Expected 'inherits' or '{{' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                6 => {
-                    match_single!(curr, Token::LeftBrace, states, 8);
-                },
-                7 => {
-                    match_type!(curr, states, 9, types);
-                },
-                8 => {
-                    match *curr {
-                        Token::RightBrace => {
-                            states.push(10);
-                            should_consume = false;
-                        },
-                        Token::Identifier(_) => {
-                            states.push(10);
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Expected 'identifier' or '}}' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                9 => {
-                    // 3:      I -> inherits TYPE
-                    reduce!(states, 3, 2);
-                    should_consume = false;
-                },
-                10 => {
-                    match *curr {
-                        Token::RightBrace => {
-                            states.push(11);
-                        },
-                        Token::Identifier(ref id) => {
-                            states.push(12);
-                            identifiers.push(id.clone());
-                        },
-                        ref e @ _ => {
-                            println!("Expected 'identifier' or '}}' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                11 => {
-                    let inherits_from = types.pop().unwrap();
-                    let class_name = types.pop().unwrap();
-                    last_class = Some(Box::new(ast::Class {
-                        name: class_name,
-                        parent: inherits_from,
-                        features: features
-                    }));
-                    features = Vec::new();
-                    // 2:      C -> class TYPE I { F }
-                    reduce!(states, 2, 6);
-                    should_consume = false;
-                },
-                12 => {
-                    match *curr {
-                        Token::LeftParen => {
-                            states.push(13);
-                        },
-                        Token::Colon => {
-                            states.push(14);
-                        },
-                        ref e @ _ => {
-                            println!("Expected '(' or ':' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                13 => {
-                    match *curr {
-                        Token::RightParen => {
-                            states.push(125);
-                        },
-                        Token::Identifier(_) => {
-                            states.push(15);
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Expected ')' or 'identifier' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                14 => {
-                    match_type!(curr, states, 17, types);
-                },
-                15 => {
-                    match_identifier!(curr, states, 16, identifiers);
-                },
-                16 => {
-                    match_single!(curr, Token::Colon, states, 19);
-                },
-                17 => {
-                    match *curr {
-                        Token::Assign => {
-                            states.push(28);
-                        },
-                        Token::Semicolon => {
-                            states.push(21);
-                            expressions.push(Box::new(ast::Expression::NoExpr));
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Expected '<-' or ';' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                18 => {
-                    match *curr {
-                        Token::RightParen => {
-                            // 8:      R -> X A
-                            reduce!(states, 8, 2);
-                            should_consume = false;
-                        },
-                        Token::Comma => {
-                            states.push(22);
-                        },
-                        ref e @ _ => {
-                            println!("Expected ')' or ',' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                19 => {
-                    match_type!(curr, states, 20, types);
-                },
-                20 => {
-                    let formal_type = types.pop().unwrap();
-                    let formal_name = identifiers.pop().unwrap();
-                    formals.push(ast::Formal {
-                        name: formal_name,
-                        cool_type: formal_type
-                    });
-                    // 10:     A -> id : TYPE
-                    reduce!(states, 10, 3);
-                    should_consume = false;
-                },
-                21 => {
-                    match_single!(curr, Token::Semicolon, states, 24);
-                },
-                22 => {
-                    // 11:     X -> X A ,
-                    reduce!(states, 11, 3);
-                    should_consume = false;
-                },
-                23 => {
-                    match_single!(curr, Token::Colon, states, 26);
-                },
-                24 => {
-                    let init = expressions.pop().unwrap();
-                    let var_type = types.pop().unwrap();
-                    let var_name = identifiers.pop().unwrap();
-                    features.push(Box::new(ast::Feature::Attribute{
-                        name: var_name,
-                        cool_type: var_type,
-                        expr: init
-                    }));
-                    // 6:      F -> F id : TYPE W ;
-                    reduce!(states, 6, 6);
-                    should_consume = false;
-                },
-                26 => {
-                    match_type!(curr, states, 27, types);
-                },
-                27 => {
-                    match_single!(curr, Token::LeftBrace, states, 126);
-                },
-                28 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                29 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { states.push(51); },
-                        Token::LessThanEqual => { states.push(52); },
-                        Token::Equal => { states.push(53); },
-                        Token::Semicolon => {
-                            // 13:     W -> <- E
-                            reduce!(states, 13, 2);
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Errored in state {}, got {:?}", states.last().unwrap(), e);
-                            return None;
-                        }
-                    }
-                },
-                30 => {
-                    match *curr {
-                        Token::Assign => {
-                            states.push(54);
-                        },
-                        Token::LeftParen => {
-                            states.push(55);
-                            expression_lists.push(Vec::new());
-                        },
-                        _ => {
-                            expressions.push(Box::new(ast::Expression::Identifier(
-                                identifiers.pop().unwrap()
-                            )));
-                            // 36:     E -> <id>
-                            reduce!(states, 36, 1);
-                            should_consume = false;
-                        }
-                    }
-                },
-                31 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                32 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                33 => {
-                    match_identifier!(curr, states, 57, identifiers);
-                },
-                34 => {
-                    case_branches.push(Vec::new());
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                35 => {
-                    match_type!(curr, states, 94, types);
-                },
-                36 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                37 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                38 => {
-                    states.push(110);
-                    should_consume = false;
-                },
-                40 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                39 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                41 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        _ => {
-                            let expr = expressions.pop().unwrap();
-                            expressions.push(Box::new(ast::Expression::Negation(
-                                expr
-                            )));
-                            // 33:     E -> ~ E
-                            reduce!(states, 33, 2);
-                            should_consume = false;
-                        }
-                    }
-                },
-                42 => {
-                    // 37:     E -> <string>
-                    reduce!(states, 37, 1);
-                    should_consume = false;
-                },
-                43 => {
-                    // 38:     E -> true
-                    reduce!(states, 38, 1);
-                    should_consume = false;
-                },
-                44 => {
-                    // 39:     E -> true
-                    reduce!(states, 39, 1);
-                    should_consume = false;
-                },
-                45 => {
-                    match_single!(curr, Token::Dot, states, 101);
-                },
-                46 => {
-                    match_type!(curr, states, 124, types);
-                },
-                // Binary expressions!
-                47 ... 53 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                54 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                55 => {
-                    match *curr {
-                        Token::RightParen => {
-                            states.push(104);
-                        },
-                        _ => {
-                            states.push(96);
-                        }
-                    }
-                    should_consume = false;
-                },
-                56 => {
-                    after_expression!(curr, states, Token::Then, 73,
-                                      types, no_type, should_consume, is_statics);
-                },
-                57 => {
-                    match_single!(curr, Token::Colon, states, 58);
-                },
-                58 => {
-                    match_type!(curr, states, 59, types);
-                },
-                59 => {
-                    match *curr {
-                        Token::Assign => {
-                            states.push(28);
-                        },
-                        Token::In => {
-                            states.push(60);
-                            expressions.push(Box::new(ast::Expression::NoExpr));
-                            should_consume = false;
-                        },
-                        Token::Comma => {
-                            states.push(60);
-                            expressions.push(Box::new(ast::Expression::NoExpr));
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Expected '<-', 'in' or ',' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                60 => {
-                    match *curr {
-                        Token::In => {
-                            states.push(61);
-                        },
-                        Token::Comma => {
-                            states.push(64);
-                        },
-                        ref e @ _ => {
-                            println!("Expected 'in' or ',' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                61 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                62 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { states.push(51); },
-                        Token::LessThanEqual => { states.push(52); },
-                        Token::Equal => { states.push(53); },
-                        _ => {
-                            let body = expressions.pop().unwrap();
-                            let init = expressions.pop().unwrap();
-                            let var_type = types.pop().unwrap();
-                            let var_name = identifiers.pop().unwrap();
+    // The class names of a class header stay symbols; every other type
+    // name is read as a type.
+    fn pop_type(&mut self) -> ast::CoolType {
+        ast::CoolType::new(self.types.pop().unwrap())
+    }
 
-                            expressions.push(Box::new(ast::Expression::Let(
-                                var_name,
-                                var_type,
-                                init,
-                                body
-                            )));
+    fn literal(&mut self, token: &Token) {
+        let kind = match token.kind {
+            TokenKind::IntegerLiteral(digits) => ast::ExpressionKind::IntLiteral(digits),
+            TokenKind::StringLiteral(string) => ast::ExpressionKind::StringLiteral(string),
+            TokenKind::True => ast::ExpressionKind::True,
+            TokenKind::False => ast::ExpressionKind::False,
+            ref kind => unreachable!("{} is not a literal", kind)
+        };
+        self.push(kind, token.span);
+    }
 
-                            // 20:     E -> let id : TYPE W in E
-                            reduce!(states, 20, 7);
-                            should_consume = false;
-                        }
-                    }
-                },
-                63 => {
-                    let body = expressions.pop().unwrap();
-                    let init = expressions.pop().unwrap();
-                    let var_type = types.pop().unwrap();
-                    let var_name = identifiers.pop().unwrap();
+    fn unary(&mut self, make: fn(Box<ast::Expression>) -> ast::ExpressionKind, span: Span) {
+        let operand = self.pop();
+        self.push(make(operand), span);
+    }
 
-                    expressions.push(Box::new(ast::Expression::Let(
-                        var_name,
-                        var_type,
-                        init,
-                        body
-                    )));
+    fn binary(&mut self, op: ast::BinOp, span: Span) {
+        let right = self.pop();
+        let left = self.pop();
+        self.push(ast::ExpressionKind::BinaryOperation(op, left, right), span);
+    }
 
-                    // 21:     E -> let id : TYPE W N
-                    reduce!(states, 21, 6);
-                    should_consume = false;
-                },
-                64 => {
-                    match_identifier!(curr, states, 65, identifiers);
-                },
-                65 => {
-                    match_single!(curr, Token::Colon, states, 66);
-                },
-                66 => {
-                    match_type!(curr, states, 67, types);
-                },
-                67 => {
-                    match *curr {
-                        Token::Assign => {
-                            states.push(28);
-                        },
-                        Token::In => {
-                            states.push(68);
-                            expressions.push(Box::new(ast::Expression::NoExpr));
-                            should_consume = false;
-                        },
-                        Token::Comma => {
-                            states.push(68);
-                            expressions.push(Box::new(ast::Expression::NoExpr));
-                            should_consume = false;
-                        },
-                        ref e @ _ => {
-                            println!("Expected '<-', 'in' or ',' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                68 => {
-                    match *curr {
-                        Token::In => {
-                            states.push(152);
-                        },
-                        Token::Comma => {
-                            states.push(64);
-                        },
-                        ref e @ _ => {
-                            println!("Expected 'in' or ',' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                69 => {
-                    after_expression!(curr, states, Token::Loop, 70,
-                                      types, no_type, should_consume, is_statics);
-                },
-                70 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                71 => {
-                    after_expression!(curr, states, Token::Pool, 72,
-                                      types, no_type, should_consume, is_statics);
-                },
-                72 => {
-                    let body = expressions.pop().unwrap();
-                    let condition = expressions.pop().unwrap();
-                    expressions.push(Box::new(ast::Expression::While(
-                        condition,
-                        body
-                    )));
-                    // 19:     E -> while E loop E pool
-                    reduce!(states, 19, 5);
-                    should_consume = false;
-                },
-                73 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                74 => {
-                    after_expression!(curr, states, Token::Else, 75,
-                                      types, no_type, should_consume, is_statics);
-                },
-                75 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                76 => {
-                    after_expression!(curr, states, Token::Fi, 77,
-                                      types, no_type, should_consume, is_statics);
-                },
-                77 => {
-                    let false_branch = expressions.pop().unwrap();
-                    let true_branch = expressions.pop().unwrap();
-                    let condition = expressions.pop().unwrap();
-                    expressions.push(Box::new(ast::Expression::If(
-                        condition,
-                        true_branch,
-                        false_branch
-                    )));
-                    // 18:     E -> if E then E else E fi
-                    reduce!(states, 18, 7);
-                    should_consume = false;
-                },
-                78 => {
-                    after_expression!(curr, states, Token::Of, 79,
-                                      types, no_type, should_consume, is_statics);
-                },
-                79 => {
-                    states.push(80);
-                    should_consume = false;
-                },
-                80 => {
-                    match_identifier!(curr, states, 81, identifiers);
-                },
-                81 => {
-                    match_single!(curr, Token::Colon, states, 82);
-                },
-                82 => {
-                    match_type!(curr, states, 83, types);
-                },
-                83 => {
-                    match_single!(curr, Token::Arrow, states, 84);
-                },
-                84 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                85 => {
-                    after_expression!(curr, states, Token::Semicolon, 86,
-                                      types, no_type, should_consume, is_statics);
-                },
-                86 => {
-                    match *curr {
-                        Token::Esac => {
-                            states.push(87);
-                        },
-                        _ => {
-                            let body = expressions.pop().unwrap();
-                            let cool_type = types.pop().unwrap();
-                            let binding = identifiers.pop().unwrap();
-                            case_branches.last_mut().unwrap().push(Box::new(
-                                ast::CaseBranch{
-                                    name: binding,
-                                    cool_type: cool_type,
-                                    expr: body
-                                }
-                            ));
-                            // 49:     M -> M id : TYPE => E ;
-                            reduce!(states, 49, 7);
-                            should_consume = false;
-                        }
-                    }
-                },
-                87 => {
-                    let body = expressions.pop().unwrap();
-                    let cool_type = types.pop().unwrap();
-                    let binding = identifiers.pop().unwrap();
-                    let mut branches = case_branches.pop().unwrap();
-                    branches.push(Box::new(ast::CaseBranch{
-                        name: binding,
-                        cool_type: cool_type,
-                        expr: body
-                    }));
-                    let case_value = expressions.pop().unwrap();
-                    expressions.push(Box::new(ast::Expression::Case(
-                        case_value,
-                        branches
-                    )));
+    // Wraps the body on top of the stack in a let for the binding under it.
+    fn make_let(&mut self, span: Span) {
+        let body = self.pop();
+        let init = self.pop();
+        let cool_type = self.pop_type();
+        let name = self.identifiers.pop().unwrap();
+        self.push(ast::ExpressionKind::Let(name, cool_type, init, body), span);
+    }
+}
 
-                    // 22:     E -> case E of M id : TYPE => E ; esac
-                    reduce!(states, 22, 11);
-                    should_consume = false;
-                },
-                94 => {
-                    expressions.push(Box::new(ast::Expression::New(
-                        types.pop().unwrap()
-                    )));
-                    // 23:     E -> new TYPE
-                    reduce!(states, 23, 2);
-                    should_consume = false;
-                },
-                95 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { states.push(51); },
-                        Token::LessThanEqual => { states.push(52); },
-                        Token::Equal => { states.push(53); },
-                        _ => {
-                            let expr = expressions.pop().unwrap();
-                            let var_name = identifiers.pop().unwrap();
-                            expressions.push(Box::new(ast::Expression::Assign(
-                                var_name,
-                                expr
-                            )));
-                            // 15:     E -> id <- E
-                            reduce!(states, 15, 3);
-                            should_consume = false;
-                        }
-                    }
-                },
-                96 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                97 => {
-                    match *curr {
-                        Token::RightParen => {
-                            expression_lists.last_mut().unwrap().push(
-                                expressions.pop().unwrap()
-                            );
-                            // 41:     L -> G E
-                            reduce!(states, 41, 2);
-                            should_consume = false;
-                        },
-                        Token::Comma => {
-                            states.push(98);
-                        },
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { states.push(51); },
-                        Token::LessThanEqual => { states.push(52); },
-                        Token::Equal => { states.push(53); },
-                        ref e @ _ => {
-                            println!("Errored in state {}, got {:?}", states.last().unwrap(), e);
-                            return None;
-                        }
-                    }
-                },
-                98 => {
-                    expression_lists.last_mut().unwrap().push(
-                        expressions.pop().unwrap()
-                    );
-                    // 43:     G -> G E ,
-                    reduce!(states, 43, 3);
-                    should_consume = false;
-                },
-                101 => {
-                    match_identifier!(curr, states, 102, identifiers);
-                },
-                102 => {
-                    match *curr {
-                        Token::LeftParen => {
-                            expression_lists.push(Vec::new());
-                            states.push(103);
-                        },
-                        ref e @ _ => {
-                            println!("In state 102, expected '(' but found {:?}", e);
-                            return None;
-                        }
-                    }
-                },
-                103 => {
-                    match *curr {
-                        Token::RightParen => {
-                            states.push(105);
-                        },
-                        _ => {
-                            states.push(96);
-                        }
-                    }
-                    should_consume = false;
-                },
-                104 => {
-                    match_single!(curr, Token::RightParen, states, 161);
-                },
-                105 => {
-                    match_single!(curr, Token::RightParen, states, 106);
-                },
-                106 => {
-                    let args = expression_lists.pop().unwrap();
-                    let method_name = identifiers.pop().unwrap();
-                    let obj = expressions.pop().unwrap();
-                    if is_statics.pop().unwrap() {
-                        let static_type = types.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::StaticDispatch(
-                            obj,
-                            static_type,
-                            method_name,
-                            args
-                        )));
-                    } else {
-                        expressions.push(Box::new(ast::Expression::Dispatch(
-                            obj,
-                            method_name,
-                            args
-                        )));
-                    }
-                    // 17:     E -> E T . id ( L )
-                    reduce!(states, 17, 7);
-                    should_consume = false;
-                },
-                107 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        _ => {
-                            let expr = expressions.pop().unwrap();
-                            expressions.push(Box::new(ast::Expression::IsVoid(
-                                expr
-                            )));
-                            // 24:     E -> isvoid E
-                            reduce!(states, 24, 2);
-                            should_consume = false;
-                        }
-                    }
-                },
-                108 => {
-                    after_expression!(curr, states, Token::RightParen, 109,
-                                      types, no_type, should_consume, is_statics);
-                },
-                109 => {
-                    // Don't have to pop the expression, we're just going
-                    // to put it right back
-                    // 34:     E -> ( E )
-                    reduce!(states, 34, 3);
-                    should_consume = false;
-                },
-                110 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                111 => {
-                    after_expression!(curr, states, Token::Semicolon, 112,
-                                      types, no_type, should_consume, is_statics);
-                },
-                112 => {
-                    match *curr {
-                        Token::RightBrace => {
-                            states.push(113);
-                        },
-                        _ => {
-                            expression_lists.last_mut().unwrap().push(
-                                expressions.pop().unwrap()
-                            );
-                            // 51:     B -> B E ;
-                            reduce!(states, 51, 3);
-                            should_consume = false;
-                        }
-                    }
-                },
-                113 => {
-                    let mut body = expression_lists.pop().unwrap();
-                    body.push(expressions.pop().unwrap());
-                    expressions.push(Box::new(ast::Expression::Block(
-                        body
-                    )));
+// The stack of LR states. Alongside each state we remember the index of
+// the first token it covers, so that reducing a rule can work out the
+// span of source text the rule matched, and the sizes of the value
+// stacks just after it was pushed, so that errors can unwind to it.
+struct StateStack {
+    states: Vec<usize>,
+    starts: Vec<usize>,
+    marks: Vec<Option<ValueMarks>>,
+    // Index of the token currently being looked at
+    position: usize
+}
 
-                    // 35:     E -> { B E ; }
-                    reduce!(states, 35, 5);
-                    should_consume = false;
-                }
-                116 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { states.push(51); },
-                        Token::LessThanEqual => { states.push(52); },
-                        Token::Equal => { states.push(53); },
-                        _ => {
-                            let expr = expressions.pop().unwrap();
-                            expressions.push(Box::new(ast::Expression::Not(
-                                expr
-                            )));
-                            // 25:     E -> not E
-                            reduce!(states, 25, 2);
-                            should_consume = false;
-                        }
-                    }
-                },
-                117 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { should_reduce = true },
-                        Token::Minus => { should_reduce = true },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { should_reduce = true },
-                        Token::LessThanEqual => { should_reduce = true },
-                        Token::Equal => { should_reduce = true },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::Plus,
-                            left,
-                            right
-                        )));
-                        // 26:     E -> E + E
-                        reduce!(states, 26, 3);
-                        should_consume = false;
-                    }
-                },
-                118 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { should_reduce = true },
-                        Token::Minus => { should_reduce = true },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { should_reduce = true },
-                        Token::LessThanEqual => { should_reduce = true },
-                        Token::Equal => { should_reduce = true },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::Minus,
-                            left,
-                            right
-                        )));
-                        // 27:     E -> E - E
-                        reduce!(states, 27, 3);
-                        should_consume = false;
-                    }
-                },
-                119 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { should_reduce = true },
-                        Token::Minus => { should_reduce = true },
-                        Token::Times => { should_reduce = true },
-                        Token::Divide => { should_reduce = true },
-                        Token::LessThan => { should_reduce = true },
-                        Token::LessThanEqual => { should_reduce = true },
-                        Token::Equal => { should_reduce = true },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::Mult,
-                            left,
-                            right
-                        )));
-                        // 28:     E -> E * E
-                        reduce!(states, 28, 3);
-                        should_consume = false;
-                    }
-                },
-                120 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { should_reduce = true },
-                        Token::Minus => { should_reduce = true },
-                        Token::Times => { should_reduce = true },
-                        Token::Divide => { should_reduce = true },
-                        Token::LessThan => { should_reduce = true },
-                        Token::LessThanEqual => { should_reduce = true },
-                        Token::Equal => { should_reduce = true },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::Divide,
-                            left,
-                            right
-                        )));
-                        // 29:     E -> E / E
-                        reduce!(states, 29, 3);
-                        should_consume = false;
-                    }
-                },
-                121 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan |
-                        Token::LessThanEqual |
-                        Token::Equal => {
-                            println!("Errored in state {:?}, the binary operators do not associate.", states.last().unwrap());
-                            return None;
-                        },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::LessThan,
-                            left,
-                            right
-                        )));
-                        // 30:     E -> E < E
-                        reduce!(states, 30, 3);
-                        should_consume = false;
-                    }
-                },
-                122 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan |
-                        Token::LessThanEqual |
-                        Token::Equal => {
-                            println!("Errored in state {:?}, the binary operators do not associate.", states.last().unwrap());
-                            return None;
-                        },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::LessThanEqual,
-                            left,
-                            right
-                        )));
-                        // 31:     E -> E <= E
-                        reduce!(states, 31, 3);
-                        should_consume = false;
-                    }
-                },
-                123 => {
-                    let mut should_reduce = false;
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(45);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan |
-                        Token::LessThanEqual |
-                        Token::Equal => {
-                            println!("Errored in state {:?}, the binary operators do not associate.", states.last().unwrap());
-                            return None;
-                        },
-                        _ => { should_reduce = true; }
-                    }
-                    if should_reduce {
-                        let right = expressions.pop().unwrap();
-                        let left = expressions.pop().unwrap();
-                        expressions.push(Box::new(ast::Expression::BinaryOperation(
-                            ast::BinOp::Equal,
-                            left,
-                            right
-                        )));
-                        // 32:     E -> E = E
-                        reduce!(states, 32, 3);
-                        should_consume = false;
-                    }
-                },
-                124 => {
-                    // 45:     T -> @ TYPE
-                    reduce!(states, 45, 2);
-                    should_consume = false;
-                },
-                125 => {
-                    match_single!(curr, Token::RightParen, states, 23);
-                },
-                126 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                127 => {
-                    after_expression!(curr, states, Token::RightBrace, 128,
-                                      types, no_type, should_consume, is_statics);
-                },
-                128 => {
-                    match_single!(curr, Token::Semicolon, states, 129);
-                },
-                129 => {
-                    let fun_body = expressions.pop().unwrap();
-                    let return_type = types.pop().unwrap();
-                    let fun_name = identifiers.pop().unwrap();
-                    features.push(Box::new(ast::Feature::Method{
-                        name: fun_name,
-                        params: formals,
-                        return_type: return_type,
-                        body: fun_body
-                    }));
-                    formals = Vec::new();
-                    // 5:      F -> F id ( R ) : TYPE { E } ;
-                    reduce!(states, 5, 11);
-                    should_consume = false;
-                },
-                150 => {
-                    match_single!(curr, Token::Semicolon, states, 151);
-                },
-                151 => {
-                    class_lists.push(last_class.unwrap());
-                    last_class = None;
-                    // 1:      P -> C ;
-                    reduce!(states, 0, 2);
-                    should_consume = false;
-                },
-                152 => {
-                    expression_start!(curr, states, identifiers, expressions, expression_lists);
-                },
-                153 => {
-                    match *curr {
-                        Token::Dot => {
-                            is_statics.push(false);
-                            states.push(101);
-                            should_consume = false;
-                        },
-                        Token::At => {
-                            is_statics.push(true);
-                            states.push(46);
-                        },
-                        Token::Plus => { states.push(47); },
-                        Token::Minus => { states.push(48); },
-                        Token::Times => { states.push(49); },
-                        Token::Divide => { states.push(50); },
-                        Token::LessThan => { states.push(51); },
-                        Token::LessThanEqual => { states.push(52); },
-                        Token::Equal => { states.push(53); },
-                        _ => {
-                            let body = expressions.pop().unwrap();
-                            let init = expressions.pop().unwrap();
-                            let var_type = types.pop().unwrap();
-                            let var_name = identifiers.pop().unwrap();
+impl StateStack {
+    fn new() -> StateStack {
+        StateStack {
+            states: vec![0],
+            starts: vec![0],
+            marks: vec![None],
+            position: 0
+        }
+    }
 
-                            expressions.push(Box::new(ast::Expression::Let(
-                                var_name,
-                                var_type,
-                                init,
-                                body
-                            )));
+    fn push_from(&mut self, state: usize, start: usize) {
+        self.states.push(state);
+        self.starts.push(start);
+        self.marks.push(None);
+    }
 
-                            // 47:     N -> , id : TYPE W in E
-                            reduce!(states, 47, 7);
-                            should_consume = false;
-                        }
-                    }
-                },
-                154 => {
-                    let body = expressions.pop().unwrap();
-                    let init = expressions.pop().unwrap();
-                    let var_type = types.pop().unwrap();
-                    let var_name = identifiers.pop().unwrap();
+    fn truncate(&mut self, len: usize) {
+        self.states.truncate(len);
+        self.starts.truncate(len);
+        self.marks.truncate(len);
+    }
 
-                    expressions.push(Box::new(ast::Expression::Let(
-                        var_name,
-                        var_type,
-                        init,
-                        body
-                    )));
+    fn top(&self) -> usize {
+        *self.states.last().unwrap()
+    }
 
-                    // 48:     N -> , id : TYPE W N
-                    reduce!(states, 48, 6);
-                    should_consume = false;
-                },
-                160 => {
-                    // 40:     E -> <int literal>
-                    reduce!(states, 40, 1);
-                    should_consume = false;
-                },
-                161 => {
-                    expressions.push(Box::new(ast::Expression::Dispatch(
-                        Box::new(ast::Expression::Identifier(self_obj.clone())),
-                        identifiers.pop().unwrap(),
-                        expression_lists.pop().unwrap()
-                    )));
-                    // 16:     E -> id ( L )
-                    reduce!(states, 16, 4);
-                    should_consume = false;
-                },
-                _ => { println!("Haven't implemented state {} yet", states.last().unwrap()); }
+    // Records the value stack sizes for every state pushed since the
+    // last call.
+    fn fill_marks(&mut self, marks: ValueMarks) {
+        for mark in self.marks.iter_mut().rev() {
+            if mark.is_some() {
+                break;
             }
+            *mark = Some(marks);
         }
-        if should_consume {
-            let _ = tokens_iter.next();
+    }
+
+    // Index of the first token covered by the top `len` states.
+    fn start_of(&self, len: usize) -> usize {
+        if len == 0 {
+            self.position
+        } else {
+            self.starts[self.starts.len() - len]
         }
     }
 
-    return Some(ast::Program {
-        classes: class_lists
-    });
+    // The span covered by the top `len` states, i.e. the right hand side
+    // of the rule we're about to reduce.
+    fn span(&self, tokens: &[Token], len: usize) -> Span {
+        let first = self.start_of(len);
+        if first == self.position {
+            tokens[first].span.empty_before()
+        } else {
+            tokens[first].span.to(tokens[self.position - 1].span)
+        }
+    }
 }
 
-fn goto(state: i32, rule: i32) -> i32 {
-    println!("goto {} {}", state, rule);
-    match state {
-        0 => {
-            match rule {
-                0 | 1 => 1,
-                2 => 150,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
-            }
-        },
-        1 => {
-            match rule {
-                2 => 2,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
-            }
-        },
-        5 => {
-            match rule {
-                3 => 6,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
-            }
-        },
-        8 => {
-            match rule {
-                5 => 10,
-                6 => 10,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
-            }
-        },
-        13 => {
-            match rule {
-                8 => 125,
-                11 => 15,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
-            }
-        },
-        15 => {
-            match rule {
-                10 => 18,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+// Where to describe every step of the parse, if anywhere.
+type Trace<'a> = Option<&'a mut dyn Write>;
+
+// Writes one line of the trace. A trace that can't be written isn't worth
+// stopping the parse for.
+fn trace(out: &mut Trace, args: fmt::Arguments) {
+    if let Some(ref mut out) = *out {
+        let _ = out.write_fmt(args).and_then(|_| out.write_all(b"\n"));
+    }
+}
+
+fn can_continue(state: usize, terminal: usize) -> bool {
+    match ACTIONS[state][terminal] {
+        Action::Shift(_) | Action::Reduce(_) | Action::Accept => true,
+        Action::Error | Action::Nonassoc => false
+    }
+}
+
+// Describes the tokens that could have come next, by trying each one on
+// a copy of the state stack and seeing if it gets shifted.
+fn expected(states: &[usize]) -> Vec<&'static str> {
+    let mut shifts = vec![false; TERMINAL_DESCRIPTIONS.len()];
+    for (terminal, shifts) in shifts.iter_mut().enumerate() {
+        if terminal == ERROR_TERMINAL {
+            continue;
+        }
+        let mut states = states.to_vec();
+        *shifts = loop {
+            match ACTIONS[*states.last().unwrap()][terminal] {
+                Action::Shift(_) | Action::Accept => break true,
+                Action::Error | Action::Nonassoc => break false,
+                Action::Reduce(rule) => {
+                    let (lhs, len) = RULES[rule];
+                    let len = states.len() - len;
+                    states.truncate(len);
+                    // A missing goto is reported when the parse itself
+                    // gets there; here it just means the token can't follow.
+                    match GOTOS[*states.last().unwrap()][lhs] {
+                        Some(next) => states.push(next),
+                        None => break false
+                    }
+                }
             }
-        },
-        17 => {
-            match rule {
-                13 => 21,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        };
+    }
+
+    // A group is named when all of its members are expected, in place of
+    // listing them.
+    let mut named_groups = Vec::new();
+    for &(description, members) in &GROUPS {
+        if members.iter().all(|&member| shifts[member]) {
+            named_groups.push(description);
+        }
+    }
+    let mut descriptions = Vec::new();
+    for (terminal, &shifted) in shifts.iter().enumerate() {
+        let grouped = GROUPS.iter().any(|&(description, members)| {
+            named_groups.contains(&description) && members.contains(&terminal)
+        });
+        if shifted && !grouped {
+            descriptions.push(TERMINAL_DESCRIPTIONS[terminal]);
+        }
+    }
+    descriptions.extend(named_groups);
+    descriptions
+}
+
+// The index of the token to carry on from after shifting `error` into
+// `state`: the first one at or after `position` that the state can handle,
+// at the same nesting depth as the token at `first`, where the construct
+// that failed begins. None if the construct ends before such a token turns
+// up, which leaves the error to some enclosing construct.
+fn resume_point(state: usize, first: usize, position: usize, tokens: &[Token]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(first) {
+        if i >= position && depth == 0 {
+            if can_continue(state, terminal(&token.kind)) {
+                return Some(i);
             }
-        },
-        28 => {
-            on_expression_goto!(state, rule, 29)
-        },
-        29 => {
-            after_static_goto!(state, rule)
-        },
-        31 => {
-            on_expression_goto!(state, rule, 56)
-        },
-        32 => {
-            on_expression_goto!(state, rule, 69)
-        },
-        34 => {
-            on_expression_goto!(state, rule, 78)
-        },
-        36 => {
-            on_expression_goto!(state, rule, 107)
-        },
-        37 => {
-            on_expression_goto!(state, rule, 116)
-        },
-        38 => {
-            match rule {
-                51 => 110,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+            if token.kind == TokenKind::Semicolon {
+                return None;
             }
-        },
-        39 => {
-            on_expression_goto!(state, rule, 108)
-        },
-        40 => {
-            on_expression_goto!(state, rule, 41)
-        },
-        41 => {
-            after_static_goto!(state, rule)
-        },
-        47 => {
-            on_expression_goto!(state, rule, 117)
-        },
-        48 => {
-            on_expression_goto!(state, rule, 118)
-        },
-        49 => {
-            on_expression_goto!(state, rule, 119)
-        },
-        50 => {
-            on_expression_goto!(state, rule, 120)
-        },
-        51 => {
-            on_expression_goto!(state, rule, 121)
-        },
-        52 => {
-            on_expression_goto!(state, rule, 122)
-        },
-        53 => {
-            on_expression_goto!(state, rule, 123)
-        },
-        54 => {
-            on_expression_goto!(state, rule, 95)
-        },
-        55 => {
-            match rule {
-                41 => 104,
-                43 => 96,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        }
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftBrace => depth += 1,
+            // A closer with no opener ends the construct
+            TokenKind::RightParen | TokenKind::RightBrace if depth == 0 => return None,
+            TokenKind::RightParen | TokenKind::RightBrace => depth -= 1,
+            TokenKind::Eof => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Panic-mode recovery, using the grammar's `error` rules: unwind to the
+// innermost state that can shift `error`, throwing away the values of
+// everything above it, shift `error` in place of the tokens from there to
+// where parsing can carry on, and skip to that point. Returns false if no
+// construct on the stack can carry on.
+fn recover(states: &mut StateStack, values: &mut Values, tokens: &[Token], out: &mut Trace) -> bool {
+    for index in (0 .. states.states.len()).rev() {
+        let after_error = match ACTIONS[states.states[index]][ERROR_TERMINAL] {
+            Action::Shift(next) => next,
+            _ => continue
+        };
+        let first = states.starts.get(index + 1).cloned().unwrap_or(states.position);
+        let resume = match resume_point(after_error, first, states.position, tokens) {
+            Some(resume) => resume,
+            None => continue
+        };
+        trace(out, format_args!("error, unwinding to {:?} and skipping {} tokens",
+                                &states.states[.. index + 1], resume - states.position));
+        values.truncate(states.marks[index].unwrap());
+        states.truncate(index + 1);
+        states.push_from(after_error, first);
+        states.position = resume;
+        return true;
+    }
+    trace(out, format_args!("error, nothing on the stack can recover"));
+    false
+}
+
+// Parses one file's tokens. With `trace` set, every state stack, reduction
+// and error recovery is written to stderr as it happens.
+pub fn parse_cool_program(tokens: &[Token], trace: bool) -> Result<ast::Program, ParseErrors> {
+    if trace {
+        parse_tracing(tokens, Some(&mut io::stderr()))
+    } else {
+        parse_tracing(tokens, None)
+    }
+}
+
+fn parse_tracing(tokens: &[Token], mut out: Trace) -> Result<ast::Program, ParseErrors> {
+    let mut values = Values::new();
+    let mut states = StateStack::new();
+    let mut errors: Vec<ParseError> = Vec::new();
+    // Until a token has been shifted after an error, any further errors
+    // are probably fallout from the first, so they aren't reported and
+    // the token is skipped instead.
+    let mut shifted_since_error = true;
+
+    loop {
+        states.fill_marks(values.marks());
+
+        let token = &tokens[states.position];
+        let lookahead = terminal(&token.kind);
+        trace(&mut out, format_args!("{:?}, next: {:?}", states.states, token.kind));
+        match ACTIONS[states.top()][lookahead] {
+            Action::Shift(next) => {
+                values.shift(token);
+                let position = states.position;
+                states.push_from(next, position);
+                states.position += 1;
+                shifted_since_error = true;
+            },
+            Action::Reduce(rule) => {
+                let (lhs, len) = RULES[rule];
+                let start = states.start_of(len);
+                let span = states.span(tokens, len);
+                reduce_action(&mut values, rule, span, &tokens[start .. states.position]);
+                let remaining = states.states.len() - len;
+                states.truncate(remaining);
+                let from = states.top();
+                let next = match GOTOS[from][lhs] {
+                    Some(next) => next,
+                    None => {
+                        // Only a broken table gets here, and there's no
+                        // sensible way to carry on from it.
+                        errors.push(ParseError {
+                            span: token.span,
+                            message: format!("internal parser error: no goto from state {} after {}",
+                                             from, RULE_TEXT[rule])
+                        });
+                        break;
+                    }
+                };
+                trace(&mut out, format_args!("reduce by rule {} ({}), goto {} from {}",
+                                             rule, RULE_TEXT[rule], next, from));
+                states.push_from(next, start);
+            },
+            Action::Accept => break,
+            action => {
+                if !shifted_since_error {
+                    if token.kind == TokenKind::Eof {
+                        break;
+                    }
+                    states.position += 1;
+                    continue;
+                }
+                let message = match action {
+                    Action::Nonassoc => format!(
+                        "comparison operators do not associate, so {} cannot follow a comparison",
+                        token.kind),
+                    _ => format!("expected {} but found {}", one_of(&expected(&states.states)), token.kind)
+                };
+                errors.push(ParseError { span: token.span, message });
+                shifted_since_error = false;
+                if !recover(&mut states, &mut values, tokens, &mut out) {
+                    break;
+                }
             }
-        },
-        56 => {
-            after_static_goto!(state, rule)
-        },
-        59 => {
-            match rule {
-                13 => 60,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        }
+    }
+
+    let span = tokens[0].span.to(tokens[tokens.len() - 1].span);
+    let program = ast::Program {
+        classes: values.classes,
+        span
+    };
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(ParseErrors { program, errors })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+    use source::FileId;
+    use std::fs;
+
+    fn parse(text: &str) -> Result<ast::Program, ParseErrors> {
+        let tokens = lexer::lex(text.chars().peekable(), FileId(0)).ok().unwrap();
+        parse_cool_program(&tokens, false)
+    }
+
+    fn class_names(program: &ast::Program) -> Vec<String> {
+        program.classes.iter().map(|class| class.name.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_the_example_programs() {
+        let mut paths: Vec<_> = fs::read_dir("examples").unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let text = fs::read_to_string(&path).unwrap();
+            if let Err(failure) = parse(&text) {
+                panic!("{}:{}: {}", path.display(), failure.errors[0].span.start_line,
+                       failure.errors[0].message);
             }
-        },
-        60 => {
-            match rule {
-                47 => 63,
-                48 => 63,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        }
+    }
+
+    #[test]
+    fn reports_every_bad_class() {
+        let failure = parse("
+            class A inherits { };
+            class B { x : Int; };
+            class C { y : Int <- ; };
+            class D { };
+        ").err().unwrap();
+        assert_eq!(failure.errors.len(), 2);
+        assert_eq!(failure.errors[0].span.start_line, 2);
+        assert_eq!(failure.errors[1].span.start_line, 4);
+        assert_eq!(class_names(&failure.program), vec!["B", "C", "D"]);
+    }
+
+    #[test]
+    fn recovers_at_features() {
+        let failure = parse("
+            class A {
+                f(x : Int) : Int { x + };
+                g : Int;
+                h(x : Int) : Int { (x * ) };
+                i : Bool;
+            };
+        ").err().unwrap();
+        assert_eq!(failure.errors.len(), 2);
+        let class = &failure.program.classes[0];
+        let names: Vec<String> = class.features.iter().map(|feature| {
+            match *feature {
+                ast::Feature::Method { ref name, .. } |
+                ast::Feature::Attribute { ref name, .. } => name.to_string()
             }
-        },
-        61 => {
-            on_expression_goto!(state, rule, 62)
-        },
-        62 => {
-            after_static_goto!(state, rule)
-        },
-        67 => {
-            match rule {
-                13 => 68,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        }).collect();
+        assert_eq!(names, vec!["g", "i"]);
+    }
+
+    #[test]
+    fn recovers_at_block_statements() {
+        let failure = parse("
+            class A {
+                f(x : Int) : Int { { x; x + ; x * ; x; } };
+            };
+        ").err().unwrap();
+        assert_eq!(failure.errors.len(), 2);
+        let class = &failure.program.classes[0];
+        match class.features[0] {
+            ast::Feature::Method { ref body, .. } => match body.kind {
+                ast::ExpressionKind::Block(ref statements) => assert_eq!(statements.len(), 4),
+                _ => panic!("expected a block")
+            },
+            _ => panic!("expected a method")
+        }
+    }
+
+    #[test]
+    fn recovers_at_let_bindings() {
+        let failure = parse("
+            class A {
+                f(x : Int) : Int { let a : Int <- x +, b : Int in b };
+            };
+        ").err().unwrap();
+        assert_eq!(failure.errors.len(), 1);
+        let class = &failure.program.classes[0];
+        match class.features[0] {
+            ast::Feature::Method { ref body, .. } => match body.kind {
+                ast::ExpressionKind::Let(ref name, _, _, ref rest) => {
+                    assert_eq!(name.as_str(), ERROR_IDENTIFIER);
+                    match rest.kind {
+                        ast::ExpressionKind::Let(name, _, _, _) => assert_eq!(name.as_str(), "b"),
+                        _ => panic!("expected the second binding")
+                    }
+                },
+                _ => panic!("expected a let")
+            },
+            _ => panic!("expected a method")
+        }
+    }
+
+    #[test]
+    fn messages_name_expected_tokens() {
+        let failure = parse("
+            class A inherits { };
+            class B { f(x : Int) : Int { x + }; };
+            class C { g : Int <- 1 2; };
+        ").err().unwrap();
+        let messages: Vec<&str> = failure.errors.iter().map(|error| &error.message[..]).collect();
+        assert_eq!(messages, vec![
+            "expected a type name but found '{'",
+            "expected an expression but found '}'",
+            "expected ';' or an operator but found integer 2"
+        ]);
+    }
+
+    #[test]
+    fn reports_missing_classes() {
+        let failure = parse("").err().unwrap();
+        assert_eq!(failure.errors.len(), 1);
+        assert!(failure.program.classes.is_empty());
+    }
+
+    // Writes an expression as (operator operands...), to show how it was
+    // grouped.
+    fn shape(expr: &ast::Expression) -> String {
+        let list = |head: &str, parts: Vec<String>| {
+            if parts.is_empty() {
+                format!("({})", head)
+            } else {
+                format!("({} {})", head, parts.join(" "))
             }
-        },
-        68 => {
-            match rule {
-                47 => 154,
-                48 => 154,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        };
+        let shapes = |exprs: &[ast::Expression]| exprs.iter().map(shape).collect::<Vec<_>>();
+        match expr.kind {
+            ast::ExpressionKind::Assign(ref name, ref value) => list("<-", vec![name.to_string(), shape(value)]),
+            ast::ExpressionKind::Dispatch(ref object, ref method, ref args) => {
+                let mut parts = vec![shape(object), method.to_string()];
+                parts.extend(shapes(args));
+                list(".", parts)
+            },
+            ast::ExpressionKind::StaticDispatch(ref object, ref static_type, ref method, ref args) => {
+                let mut parts = vec![shape(object), static_type.to_string(), method.to_string()];
+                parts.extend(shapes(args));
+                list("@", parts)
+            },
+            ast::ExpressionKind::If(ref condition, ref then_branch, ref else_branch) => {
+                list("if", vec![shape(condition), shape(then_branch), shape(else_branch)])
+            },
+            ast::ExpressionKind::While(ref condition, ref body) => list("while", vec![shape(condition), shape(body)]),
+            ast::ExpressionKind::Let(ref name, ref cool_type, ref init, ref body) => {
+                list("let", vec![name.to_string(), cool_type.to_string(), shape(init), shape(body)])
+            },
+            ast::ExpressionKind::Case(ref value, ref branches) => {
+                let mut parts = vec![shape(value)];
+                for branch in branches {
+                    parts.push(format!("({} {} {})", branch.name, branch.cool_type, shape(&branch.expr)));
+                }
+                list("case", parts)
+            },
+            ast::ExpressionKind::Block(ref statements) => list("{", shapes(statements)),
+            ast::ExpressionKind::New(ref cool_type) => list("new", vec![cool_type.to_string()]),
+            ast::ExpressionKind::IsVoid(ref operand) => list("isvoid", vec![shape(operand)]),
+            ast::ExpressionKind::BinaryOperation(ref op, ref left, ref right) => {
+                let op = match *op {
+                    ast::BinOp::Plus => "+",
+                    ast::BinOp::Minus => "-",
+                    ast::BinOp::Mult => "*",
+                    ast::BinOp::Divide => "/",
+                    ast::BinOp::LessThan => "<",
+                    ast::BinOp::LessThanEqual => "<=",
+                    ast::BinOp::Equal => "="
+                };
+                list(op, vec![shape(left), shape(right)])
+            },
+            ast::ExpressionKind::Negation(ref operand) => list("~", vec![shape(operand)]),
+            ast::ExpressionKind::Not(ref operand) => list("not", vec![shape(operand)]),
+            ast::ExpressionKind::Identifier(ref name) => name.to_string(),
+            ast::ExpressionKind::IntLiteral(value) => value.to_string(),
+            ast::ExpressionKind::StringLiteral(ref string) => format!("{:?}", string),
+            ast::ExpressionKind::True => "true".to_string(),
+            ast::ExpressionKind::False => "false".to_string(),
+            ast::ExpressionKind::NoExpr => "_".to_string()
+        }
+    }
+
+    // Every case in tests/golden/precedence.txt must parse to the tree
+    // written next to it, with both parsers.
+    #[test]
+    fn precedence_matches_the_golden_corpus() {
+        let corpus = fs::read_to_string("tests/golden/precedence.txt").unwrap();
+        let mut cases: Vec<(String, String)> = Vec::new();
+        for line in corpus.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
-        },
-        69 => {
-            after_static_goto!(state, rule)
-        },
-        70 => {
-            on_expression_goto!(state, rule, 71)
-        },
-        71 => {
-            after_static_goto!(state, rule)
-        },
-        73 => {
-            on_expression_goto!(state, rule, 74)
-        },
-        74 => {
-            after_static_goto!(state, rule)
-        },
-        75 => {
-            on_expression_goto!(state, rule, 76)
-        },
-        76 => {
-            after_static_goto!(state, rule)
-        },
-        78 => {
-            after_static_goto!(state, rule)
-        },
-        79 => {
-            match rule {
-                49 => 80,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+            let mut halves = line.splitn(2, "-->");
+            let source = halves.next().unwrap().trim();
+            match halves.next() {
+                // A tree on a line of its own goes with the expression above
+                Some(tree) if source.is_empty() => cases.last_mut().unwrap().1 = tree.trim().to_string(),
+                Some(tree) => cases.push((source.to_string(), tree.trim().to_string())),
+                None => cases.push((source.to_string(), String::new()))
             }
-        },
-        84 => {
-            on_expression_goto!(state, rule, 85)
-        },
-        85 => {
-            after_static_goto!(state, rule)
-        },
-        95 => {
-            after_static_goto!(state, rule)
-        },
-        96 => {
-            on_expression_goto!(state, rule, 97)
-        },
-        97 => {
-            after_static_goto!(state, rule)
-        },
-        103 => {
-            match rule {
-                41 => 105,
-                43 => 96,
-                _ => panic!("GOTO PANIC IN STATE {} AFTER REDUCING RULE {}", state, rule)
+        }
+        assert!(!cases.is_empty());
+
+        for (source, tree) in &cases {
+            let text = format!("class A {{ f() : Object {{ {} }}; }};", source);
+            let tokens = lexer::lex(text.chars().peekable(), FileId(0)).ok().unwrap();
+            let parsers: [(&str, Result<ast::Program, ParseErrors>); 2] = [
+                ("LALR", parse_cool_program(&tokens, false)),
+                ("recursive-descent", descent::parse_cool_program(&tokens))
+            ];
+            for &(name, ref result) in &parsers {
+                let program = match *result {
+                    Ok(ref program) => program,
+                    Err(ref failure) => panic!("{}: the {} parser failed: {}", source, name, failure.errors[0].message)
+                };
+                match program.classes[0].features[0] {
+                    ast::Feature::Method { ref body, .. } => {
+                        assert_eq!(shape(body), *tree, "{} with the {} parser", source, name)
+                    },
+                    _ => panic!("expected a method")
+                }
             }
-        },
-        107 => {
-            after_static_goto!(state, rule)
-        },
-        108 => {
-            after_static_goto!(state, rule)
-        },
-        110 => {
-            on_expression_goto!(state, rule, 111)
-        },
-        111 => {
-            after_static_goto!(state, rule)
-        },
-        116 => {
-            after_static_goto!(state, rule)
-        },
-        117 => {
-            after_static_goto!(state, rule)
-        },
-        118 => {
-            after_static_goto!(state, rule)
-        },
-        119 => {
-            after_static_goto!(state, rule)
-        },
-        120 => {
-            after_static_goto!(state, rule)
-        },
-        121 => {
-            after_static_goto!(state, rule)
-        },
-        122 => {
-            after_static_goto!(state, rule)
-        },
-        123 => {
-            after_static_goto!(state, rule)
-        },
-        126 => {
-            on_expression_goto!(state, rule, 127)
-        },
-        127 => {
-            after_static_goto!(state, rule)
-        },
-        152 => {
-            on_expression_goto!(state, rule, 153)
-        },
-        153 => {
-            after_static_goto!(state, rule)
-        },
-        _ => {
-            panic!("Haven't implemented goto for state {} yet", state);
         }
     }
+
+    #[test]
+    fn comparisons_do_not_associate() {
+        for source in &["a < b < c", "a = b <= c", "a <= b + c = d", "not a < b = c"] {
+            let text = format!("class A {{ f() : Object {{ {} }}; }};", source);
+            let failure = parse(&text).err().unwrap();
+            assert_eq!(failure.errors.len(), 1);
+            assert!(failure.errors[0].message.starts_with("comparison operators do not associate"),
+                    "{}: {}", source, failure.errors[0].message);
+        }
+    }
+
+    // The trace of parsing some text: the lines describing the stack before
+    // each step, and the rules reduced.
+    fn trace_of(text: &str) -> (Vec<String>, Vec<String>) {
+        let tokens = lexer::lex(text.chars().peekable(), FileId(0)).ok().unwrap();
+        let mut out = Vec::new();
+        let traced = parse_tracing(&tokens, Some(&mut out)).is_ok();
+        // Tracing doesn't change the outcome.
+        assert_eq!(traced, parse_cool_program(&tokens, false).is_ok());
+        let out = String::from_utf8(out).unwrap();
+        let (reductions, others): (Vec<&str>, Vec<&str>) = out.lines().partition(|line| line.starts_with("reduce"));
+        let reductions = reductions.iter()
+            .map(|line| line[line.find('(').unwrap() + 1 .. line.rfind(')').unwrap()].to_string())
+            .collect();
+        (others.iter().map(|line| line.to_string()).collect(), reductions)
+    }
+
+    #[test]
+    fn traces_shifts_and_reductions() {
+        let (steps, reductions) = trace_of("class A { x : Int; };");
+        assert_eq!(steps[0], "[0], next: Class");
+        assert_eq!(steps.last().unwrap(), "[0, 3], next: Eof");
+        // A stack line before each shift, each reduction and the accept.
+        assert_eq!(steps.len(), 9 + reductions.len() + 1);
+        assert!(steps.iter().all(|step| step.starts_with("[0") && step.contains("], next: ")));
+        assert_eq!(reductions, vec![
+            "inherits ->",
+            "features ->",
+            "init ->",
+            "feature -> OBJECTID ':' TYPEID init ';'",
+            "features -> features feature",
+            "class -> CLASS TYPEID inherits '{' features '}'",
+            "classes -> class ';'"
+        ]);
+
+        let (steps, reductions) = trace_of("class A { x : ; };");
+        assert_eq!(steps.iter().filter(|step| step.starts_with("error, unwinding to [0, ")).count(), 1);
+        assert!(reductions.contains(&"features -> features error ';'".to_string()));
+    }
 }
@@ -1,138 +1,159 @@
+use std::fmt;
+use std::io;
+use std::io::Write;
+
 use ast::BinOp;
 use ast::CaseBranch;
 use ast::Class;
 use ast::Expression;
+use ast::ExpressionKind;
 use ast::Feature;
 use ast::Formal;
 use ast::Program;
 use lexer::Token;
+use lexer::TokenKind;
+use source::{SourceMap, Span};
+use symbol;
 
 pub trait Printable {
-    fn pretty_print(&self, tabs: u32);
+    fn pretty_print(&self, out: &mut dyn Write, tabs: u32) -> io::Result<()>;
 }
 
-impl Printable for Program {
-    fn pretty_print(&self, tabs: u32) {
-        println(tabs, "_program");
-        for class in &self.classes {
-            class.pretty_print(tabs + 1);
-        }
+// Prints a program the way the reference parser does, naming the file
+// each class came from.
+pub fn print_program(out: &mut dyn Write, program: &Program, sources: &SourceMap) -> io::Result<()> {
+    // The program's span runs to the end of the file, but the reference
+    // gives it the line its last class ends on.
+    let span = program.classes.last().map_or(program.span, |class| class.span);
+    print_line(out, 0, span)?;
+    println(out, 0, "_program")?;
+    for class in &program.classes {
+        print_class(out, class, sources, 1)?;
     }
+    Ok(())
 }
 
-impl Printable for Class {
-    fn pretty_print(&self, tabs: u32) {
-        println(tabs, "_class");
-        println(tabs + 1, &self.name);
-        println(tabs + 1, &self.parent);
-        println(tabs + 1, "(");
-        for feature in &self.features {
-            feature.pretty_print(tabs + 1);
-        }
-        println(tabs + 1, ")");
+fn print_class(out: &mut dyn Write, class: &Class, sources: &SourceMap, tabs: u32) -> io::Result<()> {
+    print_line(out, tabs, class.span)?;
+    println(out, tabs, "_class")?;
+    println(out, tabs + 1, &class.name)?;
+    println(out, tabs + 1, &class.parent)?;
+    let file_name = &sources.file(class.span.file).name;
+    println(out, tabs + 1, &format!("\"{}\"", escape_string(file_name)))?;
+    println(out, tabs + 1, "(")?;
+    for feature in &class.features {
+        feature.pretty_print(out, tabs + 1)?;
     }
+    println(out, tabs + 1, ")")?;
+    Ok(())
 }
 
 impl Printable for Feature {
-    fn pretty_print(&self, tabs: u32) {
+    fn pretty_print(&self, out: &mut dyn Write, tabs: u32) -> io::Result<()> {
         match *self {
-            Feature::Method { ref name, ref params, ref return_type, ref body } => {
-                println(tabs, "_method");
-                println(tabs + 1, name);
+            Feature::Method { ref name, ref params, ref return_type, ref body, span } => {
+                print_line(out, tabs, span)?;
+                println(out, tabs, "_method")?;
+                println(out, tabs + 1, name)?;
                 for param in params {
-                    println(tabs + 1, "_formal");
-                    param.pretty_print(tabs + 2);
+                    param.pretty_print(out, tabs + 1)?;
                 }
-                println(tabs + 1, return_type);
-                body.pretty_print(tabs + 1);
-            },
-            Feature::Attribute { ref name, ref cool_type, ref expr } => {
-                println(tabs, "_attr");
-                println(tabs + 1, name);
-                println(tabs + 1, cool_type);
-                expr.pretty_print(tabs + 1);
+                println(out, tabs + 1, return_type)?;
+                body.pretty_print(out, tabs + 1)?;
+            },
+            Feature::Attribute { ref name, ref cool_type, ref expr, span } => {
+                print_line(out, tabs, span)?;
+                println(out, tabs, "_attr")?;
+                println(out, tabs + 1, name)?;
+                println(out, tabs + 1, cool_type)?;
+                expr.pretty_print(out, tabs + 1)?;
             }
         }
+        Ok(())
     }
 }
 
 impl Printable for Formal {
-    fn pretty_print(&self, tabs: u32) {
-        println(tabs, &self.name);
-        println(tabs, &self.cool_type);
+    fn pretty_print(&self, out: &mut dyn Write, tabs: u32) -> io::Result<()> {
+        print_line(out, tabs, self.span)?;
+        println(out, tabs, "_formal")?;
+        println(out, tabs + 1, &self.name)?;
+        println(out, tabs + 1, &self.cool_type)?;
+        Ok(())
     }
 }
 
 impl Printable for Expression {
-    fn pretty_print(&self, tabs: u32) {
-        match *self {
-            Expression::Assign(ref var, ref expr) => {
-                println(tabs, "_assign");
-                println(tabs + 1, var);
-                expr.pretty_print(tabs + 1);
-            },
-            Expression::Dispatch(ref obj, ref name, ref args) => {
-                println(tabs, "_dispatch");
-                obj.pretty_print(tabs + 1);
-                println(tabs + 1, name);
-                println(tabs + 1, "(");
+    fn pretty_print(&self, out: &mut dyn Write, tabs: u32) -> io::Result<()> {
+        print_line(out, tabs, self.span)?;
+        match self.kind {
+            ExpressionKind::Assign(ref var, ref expr) => {
+                println(out, tabs, "_assign")?;
+                println(out, tabs + 1, var)?;
+                expr.pretty_print(out, tabs + 1)?;
+            },
+            ExpressionKind::Dispatch(ref obj, ref name, ref args) => {
+                println(out, tabs, "_dispatch")?;
+                obj.pretty_print(out, tabs + 1)?;
+                println(out, tabs + 1, name)?;
+                println(out, tabs + 1, "(")?;
                 for arg in args {
-                    arg.pretty_print(tabs + 1);
+                    arg.pretty_print(out, tabs + 1)?;
                 }
-                println(tabs + 1, ")");
-            },
-            Expression::StaticDispatch(ref obj, ref cool_type, ref name, ref args) => {
-                println(tabs, "_static_dispatch");
-                obj.pretty_print(tabs + 1);
-                println(tabs + 1, cool_type);
-                println(tabs + 1, name);
-                println(tabs + 1, "(");
+                println(out, tabs + 1, ")")?;
+            },
+            ExpressionKind::StaticDispatch(ref obj, ref cool_type, ref name, ref args) => {
+                println(out, tabs, "_static_dispatch")?;
+                obj.pretty_print(out, tabs + 1)?;
+                println(out, tabs + 1, cool_type)?;
+                println(out, tabs + 1, name)?;
+                println(out, tabs + 1, "(")?;
                 for arg in args {
-                    arg.pretty_print(tabs + 1);
+                    arg.pretty_print(out, tabs + 1)?;
                 }
-                println(tabs + 1, ")");
-            },
-            Expression::If(ref cond, ref true_branch, ref false_branch) => {
-                println(tabs, "_cond");
-                cond.pretty_print(tabs + 1);
-                true_branch.pretty_print(tabs + 1);
-                false_branch.pretty_print(tabs + 1);
-            },
-            Expression::While(ref cond, ref body) => {
-                println(tabs, "_loop");
-                cond.pretty_print(tabs + 1);
-                body.pretty_print(tabs + 1);
-            },
-            Expression::Let(ref var, ref cool_type, ref init, ref body) => {
-                println(tabs, "_let");
-                println(tabs + 1, var);
-                println(tabs + 1, cool_type);
-                init.pretty_print(tabs + 1);
-                body.pretty_print(tabs + 1);
-            },
-            Expression::Case(ref expr, ref branches) => {
-                println(tabs, "_typcase");
-                expr.pretty_print(tabs + 1);
+                println(out, tabs + 1, ")")?;
+            },
+            ExpressionKind::If(ref cond, ref true_branch, ref false_branch) => {
+                println(out, tabs, "_cond")?;
+                cond.pretty_print(out, tabs + 1)?;
+                true_branch.pretty_print(out, tabs + 1)?;
+                false_branch.pretty_print(out, tabs + 1)?;
+            },
+            ExpressionKind::While(ref cond, ref body) => {
+                println(out, tabs, "_loop")?;
+                cond.pretty_print(out, tabs + 1)?;
+                body.pretty_print(out, tabs + 1)?;
+            },
+            ExpressionKind::Let(ref var, ref cool_type, ref init, ref body) => {
+                println(out, tabs, "_let")?;
+                println(out, tabs + 1, var)?;
+                println(out, tabs + 1, cool_type)?;
+                init.pretty_print(out, tabs + 1)?;
+                body.pretty_print(out, tabs + 1)?;
+            },
+            ExpressionKind::Case(ref expr, ref branches) => {
+                println(out, tabs, "_typcase")?;
+                expr.pretty_print(out, tabs + 1)?;
                 for branch in branches {
-                    branch.pretty_print(tabs + 1);
+                    branch.pretty_print(out, tabs + 1)?;
                 }
             },
-            Expression::Block(ref exprs) => {
-                println(tabs, "_block");
+            ExpressionKind::Block(ref exprs) => {
+                println(out, tabs, "_block")?;
                 for expr in exprs {
-                    expr.pretty_print(tabs + 1);
+                    expr.pretty_print(out, tabs + 1)?;
                 }
             },
-            Expression::New(ref cool_type) => {
-                println(tabs, "_new");
-                println(tabs + 1, cool_type);
+            ExpressionKind::New(ref cool_type) => {
+                println(out, tabs, "_new")?;
+                println(out, tabs + 1, cool_type)?;
             },
-            Expression::IsVoid(ref expr) => {
-                println(tabs, "_isvoid");
-                expr.pretty_print(tabs + 1);
+            ExpressionKind::IsVoid(ref expr) => {
+                println(out, tabs, "_isvoid")?;
+                expr.pretty_print(out, tabs + 1)?;
             },
-            Expression::BinaryOperation(ref op, ref left, ref right) => {
-                println(tabs, match *op {
+            ExpressionKind::BinaryOperation(ref op, ref left, ref right) => {
+                println(out, tabs, match *op {
                     BinOp::Plus => "_plus",
                     BinOp::Minus => "_sub",
                     BinOp::Mult => "_mul",
@@ -140,108 +161,276 @@ impl Printable for Expression {
                     BinOp::LessThan => "_lt",
                     BinOp::LessThanEqual => "_leq",
                     BinOp::Equal => "_eq"
-                });
-                left.pretty_print(tabs + 1);
-                right.pretty_print(tabs + 1);
+                })?;
+                left.pretty_print(out, tabs + 1)?;
+                right.pretty_print(out, tabs + 1)?;
             },
-            Expression::Negation(ref expr) => {
-                println(tabs, "_neg");
-                expr.pretty_print(tabs + 1);
+            ExpressionKind::Negation(ref expr) => {
+                println(out, tabs, "_neg")?;
+                expr.pretty_print(out, tabs + 1)?;
             },
-            Expression::Not(ref expr) => {
-                println(tabs, "_comp");
-                expr.pretty_print(tabs + 1);
+            ExpressionKind::Not(ref expr) => {
+                println(out, tabs, "_comp")?;
+                expr.pretty_print(out, tabs + 1)?;
             },
-            Expression::Identifier(ref variable) => {
-                println(tabs, "_object");
-                println(tabs + 1, variable);
+            ExpressionKind::Identifier(ref variable) => {
+                println(out, tabs, "_object")?;
+                println(out, tabs + 1, variable)?;
             },
-            Expression::IntLiteral(ref value) => {
-                println(tabs, "_int");
-                println(tabs + 1, &format!("{}", value));
+            ExpressionKind::IntLiteral(ref value) => {
+                println(out, tabs, "_int")?;
+                println(out, tabs + 1, &format!("{}", value))?;
             },
-            Expression::StringLiteral(ref value) => {
-                println(tabs, "_string");
-                println(tabs + 1, value);
+            ExpressionKind::StringLiteral(ref value) => {
+                println(out, tabs, "_string")?;
+                println(out, tabs + 1, &format!("\"{}\"", escape_string(value.as_str())))?;
             },
-            Expression::True => {
-                println(tabs, "_bool");
-                println(tabs + 1, "1");
+            ExpressionKind::True => {
+                println(out, tabs, "_bool")?;
+                println(out, tabs + 1, "1")?;
             },
-            Expression::False => {
-                println(tabs, "_bool");
-                println(tabs + 1, "0");
+            ExpressionKind::False => {
+                println(out, tabs, "_bool")?;
+                println(out, tabs + 1, "0")?;
             },
-            Expression::NoExpr => {
-                println(tabs, "_no_expr");
+            ExpressionKind::NoExpr => {
+                println(out, tabs, "_no_expr")?;
             }
         }
+        // Like the reference tools, every expression ends with its type,
+        // or _no_type before the type checker has run.
+        let static_type = self.static_type.as_ref().map_or(symbol::NO_TYPE, |cool_type| cool_type.name());
+        println(out, tabs, &format!(": {}", static_type))
     }
 }
 
 impl Printable for CaseBranch {
-    fn pretty_print(&self, tabs: u32) {
-        println(tabs, "_branch");
-        println(tabs + 1, &self.name);
-        println(tabs + 1, &self.cool_type);
-        self.expr.pretty_print(tabs + 1);
+    fn pretty_print(&self, out: &mut dyn Write, tabs: u32) -> io::Result<()> {
+        print_line(out, tabs, self.span)?;
+        println(out, tabs, "_branch")?;
+        println(out, tabs + 1, &self.name)?;
+        println(out, tabs + 1, &self.cool_type)?;
+        self.expr.pretty_print(out, tabs + 1)?;
+        Ok(())
     }
 }
 
-pub fn println(tabs: u32, string: &str) {
+// The reference tools put the line a node ends on before it.
+fn print_line(out: &mut dyn Write, tabs: u32, span: Span) -> io::Result<()> {
+    println(out, tabs, &format!("#{}", span.end_line))
+}
+
+pub fn println<T: fmt::Display + ?Sized>(out: &mut dyn Write, tabs: u32, value: &T) -> io::Result<()> {
     for _ in 0 .. tabs {
-        print!("  ");
+        write!(out, "  ")?;
     }
-    println!("{}", string);
+    writeln!(out, "{}", value)
 }
 
-impl Printable for Token {
-    fn pretty_print(&self, tabs: u32) {
-        print!("Token: ");
-        match *self {
-            Token::Type(ref class) => println!("Type: {}", class),
-            Token::Identifier(ref var) => println!("Identifier: {}", var),
-            Token::IntegerLiteral(ref val) => println!("Integer: {}", val),
-            Token::StringLiteral(ref val) => println!("String: {}", val),
-            Token::Case => println!("case"),
-            Token::Class => println!("class"),
-            Token::Else => println!("else"),
-            Token::Esac => println!("esac"),
-            Token::False => println!("false"),
-            Token::Fi => println!("fi"),
-            Token::If => println!("if"),
-            Token::In => println!("in"),
-            Token::Inherits => println!("inherits"),
-            Token::Isvoid => println!("isvoid"),
-            Token::Let => println!("let"),
-            Token::Loop => println!("loop"),
-            Token::New => println!("new"),
-            Token::Not => println!("not"),
-            Token::Of => println!("of"),
-            Token::Pool => println!("pool"),
-            Token::Then => println!("then"),
-            Token::True => println!("true"),
-            Token::While => println!("while"),
-            Token::LeftBrace => println!("leftBrace"),
-            Token::RightBrace => println!("rightBrace"),
-            Token::LeftParen => println!("leftParen"),
-            Token::RightParen => println!("rightParen"),
-            Token::Colon => println!("colon"),
-            Token::Semicolon => println!("semicolon"),
-            Token::Dot => println!("dot"),
-            Token::Comma => println!("comma"),
-            Token::At => println!("at"),
-            Token::Plus => println!("plus"),
-            Token::Minus => println!("minus"),
-            Token::Times => println!("times"),
-            Token::Divide => println!("divide"),
-            Token::Tilde => println!("tilde"),
-            Token::LessThan => println!("lessThan"),
-            Token::Equal => println!("equal"),
-            Token::LessThanEqual => println!("lessThanEqual"),
-            Token::Arrow => println!("=>"),
-            Token::Assign => println!("<-"),
-            Token::EOF => println!("<EOF>")
+// Prints the tokens of one file in the format of the reference Cool
+// lexer, so the two can be diffed directly.
+pub fn print_tokens(out: &mut dyn Write, file_name: &str, tokens: &[Token]) -> io::Result<()> {
+    writeln!(out, "#name \"{}\"", escape_string(file_name))?;
+    for token in tokens {
+        print_token(out, token)?;
+    }
+    Ok(())
+}
+
+pub fn print_token(out: &mut dyn Write, token: &Token) -> io::Result<()> {
+    let description = match token.kind {
+        TokenKind::Type(ref class) => format!("TYPEID {}", class),
+        TokenKind::Identifier(ref var) => format!("OBJECTID {}", var),
+        TokenKind::IntegerLiteral(ref val) => format!("INT_CONST {}", val),
+        TokenKind::StringLiteral(ref val) => format!("STR_CONST \"{}\"", escape_string(val.as_str())),
+        TokenKind::Error(ref error) => format!("ERROR \"{}\"", escape_string(&error.dump_text())),
+        TokenKind::True => "BOOL_CONST true".to_string(),
+        TokenKind::False => "BOOL_CONST false".to_string(),
+        TokenKind::Eof => return Ok(()),
+        ref kind => token_name(kind).to_string()
+    };
+    writeln!(out, "#{} {}", token.span.end_line, description)
+}
+
+// The reference lexer's name for tokens that don't carry a value.
+fn token_name(kind: &TokenKind) -> &'static str {
+    match *kind {
+        TokenKind::Case => "CASE",
+        TokenKind::Class => "CLASS",
+        TokenKind::Else => "ELSE",
+        TokenKind::Esac => "ESAC",
+        TokenKind::Fi => "FI",
+        TokenKind::If => "IF",
+        TokenKind::In => "IN",
+        TokenKind::Inherits => "INHERITS",
+        TokenKind::Isvoid => "ISVOID",
+        TokenKind::Let => "LET",
+        TokenKind::Loop => "LOOP",
+        TokenKind::New => "NEW",
+        TokenKind::Not => "NOT",
+        TokenKind::Of => "OF",
+        TokenKind::Pool => "POOL",
+        TokenKind::Then => "THEN",
+        TokenKind::While => "WHILE",
+        TokenKind::Arrow => "DARROW",
+        TokenKind::Assign => "ASSIGN",
+        TokenKind::LessThanEqual => "LE",
+        TokenKind::LeftBrace => "'{'",
+        TokenKind::RightBrace => "'}'",
+        TokenKind::LeftParen => "'('",
+        TokenKind::RightParen => "')'",
+        TokenKind::Colon => "':'",
+        TokenKind::Semicolon => "';'",
+        TokenKind::Dot => "'.'",
+        TokenKind::Comma => "','",
+        TokenKind::At => "'@'",
+        TokenKind::Plus => "'+'",
+        TokenKind::Minus => "'-'",
+        TokenKind::Times => "'*'",
+        TokenKind::Divide => "'/'",
+        TokenKind::Tilde => "'~'",
+        TokenKind::LessThan => "'<'",
+        TokenKind::Equal => "'='",
+        _ => unreachable!("token {:?} carries a value", kind)
+    }
+}
+
+// Escapes a string the way the reference tools print string constants:
+// quotes, backslashes and the usual control characters get backslash
+// escapes, and any other unprintable byte is written in octal.
+pub fn escape_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for &byte in string.as_bytes() {
+        match byte {
+            b'\\' => escaped.push_str("\\\\"),
+            b'"' => escaped.push_str("\\\""),
+            b'\n' => escaped.push_str("\\n"),
+            b'\t' => escaped.push_str("\\t"),
+            0x08 => escaped.push_str("\\b"),
+            0x0c => escaped.push_str("\\f"),
+            b' ' ..= b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte))
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use lexer;
+    use parser;
+    use source::FileId;
+
+    // Each example's tree should match tests/golden/trees/<name>.tree.
+    // After a deliberate change to the format, regenerate them with
+    // `cool examples/<name>.cl -o tests/golden/trees/<name>.tree`.
+    #[test]
+    fn prints_the_golden_trees() {
+        for entry in fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let text = fs::read_to_string(&path).unwrap();
+            let mut sources = SourceMap::new();
+            let file = sources.add(path.to_str().unwrap().to_string(), text.clone());
+            let tokens = lexer::lex(text.chars().peekable(), file).ok().unwrap();
+            let program = parser::parse_cool_program(&tokens, false).ok().unwrap();
+
+            let mut out = Vec::new();
+            print_program(&mut out, &program, &sources).unwrap();
+            let golden = format!("tests/golden/trees/{}.tree", path.file_stem().unwrap().to_str().unwrap());
+            let expected = fs::read_to_string(&golden).unwrap();
+            assert!(String::from_utf8(out).unwrap() == expected, "{:?} doesn't match {}", path, golden);
         }
     }
+
+    #[test]
+    fn numbers_each_node_with_the_line_it_ends_on() {
+        let text = "class A {\n  f(x : Int) : Int {\n    x +\n      2\n  };\n};\n\n";
+        let mut sources = SourceMap::new();
+        sources.add("first.cl".to_string(), String::new());
+        let file = sources.add("dir/a \"b\".cl".to_string(), text.to_string());
+        let tokens = lexer::lex(text.chars().peekable(), file).ok().unwrap();
+        let program = parser::parse_cool_program(&tokens, false).ok().unwrap();
+        let mut out = Vec::new();
+        print_program(&mut out, &program, &sources).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<String> = out.lines()
+            .filter(|line| line.trim_start().starts_with(['#', '_']))
+            .map(|line| line.trim().to_string())
+            .collect();
+        assert_eq!(lines.join(" "), "#6 _program #6 _class #5 _method #2 _formal #4 _plus #3 _object #4 _int");
+        // Each class names the file it's from, after its parent.
+        assert!(out.contains("    A\n    Object\n    \"dir/a \\\"b\\\".cl\"\n    (\n"), "{}", out);
+    }
+
+    // The text after `#line` for each token of some source.
+    fn dumped_tokens(text: &str) -> Vec<String> {
+        let tokens = match lexer::lex(text.chars().peekable(), FileId(0)) {
+            Ok(tokens) => tokens,
+            Err(failure) => failure.tokens
+        };
+        let mut out = Vec::new();
+        print_tokens(&mut out, "a.cl", &tokens).unwrap();
+        String::from_utf8(out).unwrap().lines().skip(1)
+            .map(|line| line.split_once(' ').unwrap().1.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn names_tokens_like_the_reference_lexer() {
+        let text = "case class else esac fi if in inherits isvoid let loop new not of pool then while \
+                    => <- <= { } ( ) : ; . , @ + - * / ~ < = true false";
+        assert_eq!(dumped_tokens(text).join(" "),
+                   "CASE CLASS ELSE ESAC FI IF IN INHERITS ISVOID LET LOOP NEW NOT OF POOL THEN WHILE \
+                    DARROW ASSIGN LE '{' '}' '(' ')' ':' ';' '.' ',' '@' '+' '-' '*' '/' '~' '<' '=' \
+                    BOOL_CONST true BOOL_CONST false");
+        assert_eq!(dumped_tokens("Foo foo 007 \"\" # *)"), vec![
+            "TYPEID Foo", "OBJECTID foo", "INT_CONST 007", "STR_CONST \"\"", "ERROR \"#\"", "ERROR \"Unmatched *)\""
+        ]);
+    }
+
+    #[test]
+    fn escapes_strings_like_the_reference_tools() {
+        assert_eq!(escape_string("plain text ~"), "plain text ~");
+        assert_eq!(escape_string("\\ \" \n \t \x08 \x0c"), "\\\\ \\\" \\n \\t \\b \\f");
+        // Anything else unprintable is written as octal bytes.
+        assert_eq!(escape_string("\x00\x01\r\x0b\x1b\x7f"), "\\000\\001\\015\\013\\033\\177");
+        assert_eq!(escape_string("\u{e9}"), "\\303\\251");
+    }
+
+    #[test]
+    fn prints_tokens_like_the_reference_lexer() {
+        let text = "class A {\n  s : String <- \"a\\tb\u{7f}\";\n  n : Int <- 12 * 3 <= 4;\n}; \"";
+        let tokens = lexer::lex(text.chars().peekable(), FileId(0)).err().unwrap().tokens;
+        let mut out = Vec::new();
+        print_tokens(&mut out, "dir/a \"b\".cl", &tokens).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), [
+            "#name \"dir/a \\\"b\\\".cl\"",
+            "#1 CLASS",
+            "#1 TYPEID A",
+            "#1 '{'",
+            "#2 OBJECTID s",
+            "#2 ':'",
+            "#2 TYPEID String",
+            "#2 ASSIGN",
+            "#2 STR_CONST \"a\\tb\\177\"",
+            "#2 ';'",
+            "#3 OBJECTID n",
+            "#3 ':'",
+            "#3 TYPEID Int",
+            "#3 ASSIGN",
+            "#3 INT_CONST 12",
+            "#3 '*'",
+            "#3 INT_CONST 3",
+            "#3 LE",
+            "#3 INT_CONST 4",
+            "#3 ';'",
+            "#4 '}'",
+            "#4 ';'",
+            "#4 ERROR \"EOF in string constant\"",
+            ""
+        ].join("\n"));
+    }
 }
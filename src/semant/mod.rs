@@ -0,0 +1,473 @@
+/*
+ * Semantic analysis: checks that a parsed program makes sense.
+ *
+ * The first step builds the class table, which holds every class the
+ * program can use, the basic ones included, and checks that they form a
+ * tree rooted at Object. The type checker then works out the static type
+ * of every expression, using the table to look up methods and attributes.
+ */
+
+use std::collections::HashMap;
+
+use ast;
+use source::{FileId, Span};
+use symbol::{Symbol, BOOL, INT, IO, MAIN, MAIN_METHOD, OBJECT, SELF, SELF_TYPE, STRING};
+
+mod typecheck;
+
+pub struct SemantError {
+    pub span: Span,
+    pub message: String
+}
+
+fn error(span: Span, message: String) -> SemantError {
+    SemantError { span, message }
+}
+
+pub struct Method {
+    pub name: ast::Symbol,
+    pub params: Vec<(ast::Symbol, ast::CoolType)>,
+    pub return_type: ast::CoolType
+}
+
+pub struct Attribute {
+    pub name: ast::Symbol,
+    pub cool_type: ast::CoolType
+}
+
+// What the rest of the compiler needs to know about a class without
+// looking at its code.
+pub struct ClassInfo {
+    pub name: ast::Symbol,
+    // None only for Object.
+    pub parent: Option<ast::Symbol>,
+    pub methods: Vec<Method>,
+    pub attributes: Vec<Attribute>,
+    pub basic: bool,
+    pub span: Span
+}
+
+impl ClassInfo {
+    fn new(class: &ast::Class) -> ClassInfo {
+        let mut methods = Vec::new();
+        let mut attributes = Vec::new();
+        for feature in &class.features {
+            match *feature {
+                ast::Feature::Method { ref name, ref params, ref return_type, .. } => {
+                    methods.push(Method {
+                        name: *name,
+                        params: params.iter().map(|param| (param.name, param.cool_type)).collect(),
+                        return_type: *return_type
+                    });
+                },
+                ast::Feature::Attribute { ref name, ref cool_type, .. } => {
+                    attributes.push(Attribute { name: *name, cool_type: *cool_type });
+                }
+            }
+        }
+        ClassInfo {
+            name: class.name,
+            parent: Some(class.parent),
+            methods,
+            attributes,
+            basic: false,
+            span: class.span
+        }
+    }
+
+    pub fn method(&self, name: Symbol) -> Option<&Method> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+}
+
+// The basic classes have no source, so they get an empty span at the
+// start of the first file. No error is ever reported there.
+fn basic_span() -> Span {
+    Span::new(FileId(0), (0, 0), (0, 0))
+}
+
+// A basic class with methods given as (name, [parameter types], return
+// type). Their attributes are hidden, so the table doesn't list any.
+fn basic_class(name: Symbol, parent: Option<Symbol>, methods: &[(&str, &[Symbol], Symbol)]) -> ClassInfo {
+    ClassInfo {
+        name,
+        parent,
+        methods: methods.iter().map(|&(method, params, return_type)| {
+            Method {
+                name: Symbol::id(method),
+                params: params.iter().enumerate().map(|(i, &cool_type)| {
+                    (Symbol::id(&format!("arg{}", i + 1)), ast::CoolType::new(cool_type))
+                }).collect(),
+                return_type: ast::CoolType::new(return_type)
+            }
+        }).collect(),
+        attributes: Vec::new(),
+        basic: true,
+        span: basic_span()
+    }
+}
+
+// The classes in section 8 of the Cool manual. SELF_TYPE isn't a class,
+// but it's installed alongside them so that no program can define it.
+fn basic_classes() -> Vec<ClassInfo> {
+    vec![
+        basic_class(OBJECT, None, &[
+            ("abort", &[], OBJECT),
+            ("type_name", &[], STRING),
+            ("copy", &[], SELF_TYPE)
+        ]),
+        basic_class(IO, Some(OBJECT), &[
+            ("out_string", &[STRING], SELF_TYPE),
+            ("out_int", &[INT], SELF_TYPE),
+            ("in_string", &[], STRING),
+            ("in_int", &[], INT)
+        ]),
+        basic_class(INT, Some(OBJECT), &[]),
+        basic_class(STRING, Some(OBJECT), &[
+            ("length", &[], INT),
+            ("concat", &[STRING], STRING),
+            ("substr", &[INT, INT], STRING)
+        ]),
+        basic_class(BOOL, Some(OBJECT), &[]),
+        basic_class(SELF_TYPE, Some(OBJECT), &[])
+    ]
+}
+
+// Classes that can't be inherited from.
+fn is_final(name: Symbol) -> bool {
+    name == INT || name == STRING || name == BOOL || name == SELF_TYPE
+}
+
+pub struct ClassTable {
+    classes: HashMap<ast::Symbol, ClassInfo>,
+    // Every class name, basic classes first and then in program order.
+    order: Vec<ast::Symbol>
+}
+
+impl ClassTable {
+    // Builds the table from the program's classes, leaving out any class
+    // that can't be used and reporting why.
+    fn new(program: &ast::Program, errors: &mut Vec<SemantError>) -> ClassTable {
+        let mut table = ClassTable { classes: HashMap::new(), order: Vec::new() };
+        for class in basic_classes() {
+            table.insert(class);
+        }
+
+        for class in &program.classes {
+            match table.classes.get(&class.name) {
+                Some(previous) if previous.basic => {
+                    errors.push(error(class.span, format!("Redefinition of basic class {}.", class.name)));
+                },
+                Some(_) => {
+                    errors.push(error(class.span, format!("Class {} was previously defined.", class.name)));
+                },
+                None => table.insert(ClassInfo::new(class))
+            }
+        }
+        table
+    }
+
+    fn insert(&mut self, class: ClassInfo) {
+        self.order.push(class.name);
+        self.classes.insert(class.name, class);
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<&ClassInfo> {
+        self.classes.get(&name)
+    }
+
+    pub fn contains(&self, name: Symbol) -> bool {
+        self.get(name).is_some()
+    }
+
+    // The classes in the order they were defined.
+    pub fn classes(&self) -> Vec<&ClassInfo> {
+        self.order.iter().map(|name| &self.classes[name]).collect()
+    }
+
+    // `name` and its ancestors, nearest first, ending with Object. Only
+    // for classes in a table that has passed check_inheritance.
+    pub fn ancestors(&self, name: Symbol) -> Vec<&ClassInfo> {
+        let mut ancestors = Vec::new();
+        let mut current = self.get(name);
+        while let Some(class) = current {
+            ancestors.push(class);
+            current = class.parent.and_then(|parent| self.get(parent));
+        }
+        ancestors
+    }
+
+    // Looks a method up in a class and then its ancestors.
+    pub fn find_method(&self, class: Symbol, method: Symbol) -> Option<&Method> {
+        self.ancestors(class).into_iter().filter_map(|class| class.method(method)).next()
+    }
+
+    // Checks that every parent exists and can be inherited from, and that
+    // following parents from any class leads to Object.
+    fn check_inheritance(&self, errors: &mut Vec<SemantError>) {
+        for class in self.classes().into_iter().filter(|class| !class.basic) {
+            let parent = class.parent.unwrap();
+            if is_final(parent) {
+                errors.push(error(class.span, format!("Class {} cannot inherit class {}.", class.name, parent)));
+            } else if !self.contains(parent) {
+                errors.push(error(class.span, format!(
+                    "Class {} inherits from an undefined class {}.", class.name, parent)));
+            }
+        }
+
+        for class in self.classes().into_iter().filter(|class| !class.basic) {
+            // A chain that goes on longer than there are classes has to
+            // come back round on itself. One that reaches an undefined
+            // class has already been reported, and ends there.
+            let mut current = class;
+            let mut steps = 0;
+            while let Some(parent) = current.parent.and_then(|parent| self.get(parent)) {
+                current = parent;
+                steps += 1;
+                if steps > self.order.len() {
+                    errors.push(error(class.span, format!(
+                        "Class {}, or an ancestor of {}, is involved in an inheritance cycle.",
+                        class.name, class.name)));
+                    break;
+                }
+            }
+        }
+    }
+
+    // Main has to have a main method with no arguments. The manual only asks
+    // that Main have one, so an inherited main counts: the program starts by
+    // dispatching main on a new Main, which finds it either way.
+    fn check_main(&self, program: &ast::Program, errors: &mut Vec<SemantError>) {
+        match self.get(MAIN) {
+            Some(main) if !main.basic => {
+                match self.find_method(MAIN, MAIN_METHOD) {
+                    None => {
+                        errors.push(error(main.span, format!("No '{}' method in class {}.", MAIN_METHOD, MAIN)));
+                    },
+                    Some(method) if !method.params.is_empty() => {
+                        errors.push(error(main.span, format!(
+                            "'{}' method in class {} should have no arguments.", MAIN_METHOD, MAIN)));
+                    },
+                    Some(_) => {}
+                }
+            },
+            _ => errors.push(error(program.span.empty_before(), format!("Class {} is not defined.", MAIN)))
+        }
+    }
+
+    // Checks each class's features against each other and against the
+    // features it inherits.
+    fn check_features(&self, program: &ast::Program, errors: &mut Vec<SemantError>) {
+        for class in &program.classes {
+            let mut methods = Vec::new();
+            let mut attributes = Vec::new();
+            for feature in &class.features {
+                match *feature {
+                    ast::Feature::Method { name, ref params, ref return_type, span, .. } => {
+                        if methods.contains(&name) {
+                            errors.push(error(span, format!("Method {} is multiply defined.", name)));
+                        } else {
+                            methods.push(name);
+                            self.check_override(class.parent, name, params, return_type, span, errors);
+                        }
+
+                        let mut names = Vec::new();
+                        for param in params {
+                            if param.name == SELF {
+                                errors.push(error(param.span, "'self' cannot be the name of a formal parameter.".to_string()));
+                            } else if names.contains(&param.name) {
+                                errors.push(error(param.span, format!("Formal parameter {} is multiply defined.", param.name)));
+                            } else {
+                                names.push(param.name);
+                            }
+                        }
+                    },
+                    ast::Feature::Attribute { name, span, .. } => {
+                        let inherited = self.ancestors(class.parent).iter()
+                            .any(|ancestor| ancestor.attributes.iter().any(|attribute| attribute.name == name));
+                        if name == SELF {
+                            errors.push(error(span, "'self' cannot be the name of an attribute.".to_string()));
+                        } else if attributes.contains(&name) {
+                            errors.push(error(span, format!("Attribute {} is multiply defined in class.", name)));
+                        } else if inherited {
+                            errors.push(error(span, format!("Attribute {} is an attribute of an inherited class.", name)));
+                        } else {
+                            attributes.push(name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // A method that redefines an inherited one has to keep its signature.
+    fn check_override(&self, parent: Symbol, name: Symbol, params: &[ast::Formal],
+                      return_type: &ast::CoolType, span: Span, errors: &mut Vec<SemantError>) {
+        let original = match self.find_method(parent, name) {
+            Some(original) => original,
+            None => return
+        };
+        if params.len() != original.params.len() {
+            errors.push(error(span, format!("Incompatible number of formal parameters in redefined method {}.", name)));
+        } else {
+            for (param, original_param) in params.iter().zip(&original.params) {
+                if param.cool_type != original_param.1 {
+                    errors.push(error(param.span, format!(
+                        "In redefined method {}, parameter type {} is different from original type {}.",
+                        name, param.cool_type, original_param.1)));
+                }
+            }
+        }
+        if *return_type != original.return_type {
+            errors.push(error(span, format!(
+                "In redefined method {}, return type {} is different from original return type {}.",
+                name, return_type, original.return_type)));
+        }
+    }
+}
+
+// Builds the class table for a program and type checks it, recording the
+// static type of every expression, or reports everything wrong with it.
+// Features and types are only checked once the classes themselves make
+// sense.
+pub fn check_program(program: &mut ast::Program) -> Result<ClassTable, Vec<SemantError>> {
+    let mut errors = Vec::new();
+    let table = ClassTable::new(program, &mut errors);
+    table.check_inheritance(&mut errors);
+    if errors.is_empty() {
+        table.check_main(program, &mut errors);
+        table.check_features(program, &mut errors);
+        typecheck::check_program(&table, program, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(table)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use lexer;
+    use parser;
+
+    fn check(text: &str) -> Result<ClassTable, Vec<String>> {
+        let tokens = lexer::lex(text.chars().peekable(), FileId(0)).ok().unwrap();
+        let mut program = parser::parse_cool_program(&tokens, false).ok().unwrap();
+        check_program(&mut program).map_err(|errors| {
+            errors.iter().map(|error| format!("{}: {}", error.span.start_line, error.message)).collect()
+        })
+    }
+
+    const MAIN_CLASS: &str = "class Main { main() : Object { 0 }; };\n";
+
+    #[test]
+    fn accepts_the_example_programs() {
+        for entry in fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(errors) = check(&fs::read_to_string(&path).unwrap()) {
+                panic!("{:?}: {:?}", path, errors);
+            }
+        }
+    }
+
+    #[test]
+    fn installs_the_basic_classes() {
+        let table = check(MAIN_CLASS).ok().unwrap();
+        let names: Vec<&str> = table.classes().iter().map(|class| class.name.as_str()).collect();
+        assert_eq!(names, vec!["Object", "IO", "Int", "String", "Bool", "SELF_TYPE", "Main"]);
+        assert!(table.find_method(IO, Symbol::id("copy")).is_some());
+        let substr = table.find_method(STRING, Symbol::id("substr")).unwrap();
+        let params: Vec<ast::CoolType> = substr.params.iter().map(|param| param.1).collect();
+        assert_eq!(params, vec![ast::CoolType::Named(INT), ast::CoolType::Named(INT)]);
+        assert_eq!(substr.return_type, ast::CoolType::Named(STRING));
+        assert_eq!(table.find_method(OBJECT, Symbol::id("copy")).unwrap().return_type, ast::CoolType::SelfType);
+        let ancestors: Vec<Symbol> = table.ancestors(MAIN).iter().map(|class| class.name).collect();
+        assert_eq!(ancestors, vec![MAIN, OBJECT]);
+    }
+
+    #[test]
+    fn reports_bad_class_definitions() {
+        let cases: &[(&str, &[&str])] = &[
+            ("class A {};\nclass A {};\nclass Int {};\nclass SELF_TYPE {};",
+             &["2: Class A was previously defined.",
+               "3: Redefinition of basic class Int.",
+               "4: Redefinition of basic class SELF_TYPE."]),
+            ("class A inherits B {};\nclass C inherits Bool {};\nclass D inherits SELF_TYPE {};",
+             &["1: Class A inherits from an undefined class B.",
+               "2: Class C cannot inherit class Bool.",
+               "3: Class D cannot inherit class SELF_TYPE."]),
+            ("class A inherits B {};\nclass B inherits C {};\nclass C inherits A {};\nclass D inherits C {};",
+             &["1: Class A, or an ancestor of A, is involved in an inheritance cycle.",
+               "2: Class B, or an ancestor of B, is involved in an inheritance cycle.",
+               "3: Class C, or an ancestor of C, is involved in an inheritance cycle.",
+               "4: Class D, or an ancestor of D, is involved in an inheritance cycle."]),
+            ("class A inherits A {};",
+             &["1: Class A, or an ancestor of A, is involved in an inheritance cycle."]),
+            // A bad parent doesn't hide a cycle elsewhere, and a chain that
+            // reaches one isn't a cycle.
+            ("class B inherits C {};\nclass C inherits B {};\nclass D inherits Undefined {};\nclass E inherits D {};",
+             &["3: Class D inherits from an undefined class Undefined.",
+               "1: Class B, or an ancestor of B, is involved in an inheritance cycle.",
+               "2: Class C, or an ancestor of C, is involved in an inheritance cycle."]),
+        ];
+        for &(text, expected) in cases {
+            let program = format!("{}\n{}", text, MAIN_CLASS);
+            assert_eq!(check(&program).err().unwrap(), expected, "checking {}", text);
+        }
+    }
+
+    #[test]
+    fn requires_a_main_method() {
+        assert_eq!(check("class A {};").err().unwrap(), vec!["1: Class Main is not defined."]);
+        assert_eq!(check("\nclass Main { x : Int; };").err().unwrap(), vec!["2: No 'main' method in class Main."]);
+        assert_eq!(check("class Main { main(x : Int) : Int { x }; };").err().unwrap(),
+                   vec!["1: 'main' method in class Main should have no arguments."]);
+    }
+
+    #[test]
+    fn looks_for_main_in_the_ancestors_of_main() {
+        assert!(check("class A { main() : Int { 0 }; };\nclass Main inherits A {};").is_ok());
+        assert!(check("class A { main() : Int { 0 }; };\nclass B inherits A {};\nclass Main inherits B {};").is_ok());
+        assert_eq!(check("class A { main(x : Int) : Int { x }; };\nclass Main inherits A {};").err().unwrap(),
+                   vec!["2: 'main' method in class Main should have no arguments."]);
+        // A method of a class that isn't an ancestor doesn't count.
+        assert_eq!(check("class A { main() : Int { 0 }; };\nclass Main {};").err().unwrap(),
+                   vec!["2: No 'main' method in class Main."]);
+    }
+
+    #[test]
+    fn checks_features_against_inherited_ones() {
+        let text = "class A {\n\
+                    a : Int;\n\
+                    f(x : Int, y : A) : A { self };\n\
+                    };\n\
+                    class B inherits A {\n\
+                    a : String;\n\
+                    b : Int;\n\
+                    b : Int;\n\
+                    self : Int;\n\
+                    f(x : Int, y : B) : B { self };\n\
+                    f(x : Int) : A { self };\n\
+                    g(x : Int, self : Int, x : Int) : Int { x };\n\
+                    abort() : Object { self };\n\
+                    copy(x : Int) : SELF_TYPE { self };\n\
+                    type_name() : Object { self };\n\
+                    };\n";
+        assert_eq!(check(&format!("{}{}", text, MAIN_CLASS)).err().unwrap(), vec![
+            "6: Attribute a is an attribute of an inherited class.",
+            "8: Attribute b is multiply defined in class.",
+            "9: 'self' cannot be the name of an attribute.",
+            "10: In redefined method f, parameter type B is different from original type A.",
+            "10: In redefined method f, return type B is different from original return type A.",
+            "11: Method f is multiply defined.",
+            "12: 'self' cannot be the name of a formal parameter.",
+            "12: Formal parameter x is multiply defined.",
+            "14: Incompatible number of formal parameters in redefined method copy.",
+            "15: In redefined method type_name, return type Object is different from original return type String."
+        ]);
+    }
+}
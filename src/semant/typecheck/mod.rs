@@ -0,0 +1,574 @@
+/*
+ * The type checker: applies the typing rules in section 12 of the Cool
+ * manual to every expression and records the type it computes on the
+ * expression's node.
+ *
+ * Inside class C, SELF_TYPE is the type of `self`, which the manual
+ * writes SELF_TYPE_C: it conforms to C and C's ancestors, but only
+ * SELF_TYPE conforms to it. A method declared to return SELF_TYPE returns
+ * the type of the object it was called on, and `new SELF_TYPE` makes an
+ * object of the same class as `self`.
+ *
+ * An expression that can't be given a type is reported and treated as
+ * an Object, so one mistake doesn't lead to a string of errors about the
+ * expressions around it.
+ */
+
+use ast;
+use ast::{BinOp, CoolType, ExpressionKind};
+use source::Span;
+use symbol::{Symbol, BOOL, INT, NO_TYPE, OBJECT, SELF, STRING};
+
+use super::{error, ClassTable, SemantError};
+
+fn cool_type(name: Symbol) -> CoolType {
+    CoolType::new(name)
+}
+
+// Int, String and Bool can only be compared with each other.
+fn is_basic_value(cool_type: &CoolType) -> bool {
+    let name = cool_type.name();
+    name == INT || name == STRING || name == BOOL
+}
+
+fn operator(op: &BinOp) -> &'static str {
+    match *op {
+        BinOp::Plus => "+",
+        BinOp::Minus => "-",
+        BinOp::Mult => "*",
+        BinOp::Divide => "/",
+        BinOp::LessThan => "<",
+        BinOp::LessThanEqual => "<=",
+        BinOp::Equal => "="
+    }
+}
+
+struct TypeChecker<'a> {
+    table: &'a ClassTable,
+    errors: &'a mut Vec<SemantError>,
+    // The class being checked.
+    class: Symbol,
+    // The identifiers in scope with their types. Later entries hide
+    // earlier ones with the same name.
+    scope: Vec<(Symbol, CoolType)>
+}
+
+impl<'a> TypeChecker<'a> {
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(error(span, message));
+    }
+
+    // The type itself, or Object if it names an undefined class, which is
+    // reported elsewhere.
+    fn known(&self, given: &CoolType) -> CoolType {
+        match *given {
+            CoolType::Named(name) if name != NO_TYPE && !self.table.contains(name) => cool_type(OBJECT),
+            _ => *given
+        }
+    }
+
+    // Like known, but reports a type written in the program that doesn't
+    // name a class.
+    fn declared(&mut self, given: &CoolType, span: Span, message: String) -> CoolType {
+        if let CoolType::Named(name) = *given {
+            if !self.table.contains(name) {
+                self.error(span, message);
+            }
+        }
+        self.known(given)
+    }
+
+    // The class whose methods an object of this type has. SELF_TYPE_C
+    // could be any subclass of C, but C's methods are the ones we know of.
+    fn class_of(&self, given: &CoolType) -> Symbol {
+        match *given {
+            CoolType::Named(name) => name,
+            CoolType::SelfType => self.class
+        }
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<CoolType> {
+        self.scope.iter().rev().find(|binding| binding.0 == name).map(|binding| binding.1)
+    }
+
+    // Whether a value of type `sub` can be used where `sup` is expected.
+    fn conforms(&self, sub: &CoolType, sup: &CoolType) -> bool {
+        if sub.name() == NO_TYPE {
+            return true;
+        }
+        match *sup {
+            // SELF_TYPE_C could be any subclass of C, so only it conforms
+            // to itself.
+            CoolType::SelfType => *sub == CoolType::SelfType,
+            CoolType::Named(sup) => {
+                self.table.ancestors(self.class_of(sub)).iter().any(|class| class.name == sup)
+            }
+        }
+    }
+
+    // The least upper bound of two types: their nearest common ancestor,
+    // with SELF_TYPE_C standing in for C unless both are SELF_TYPE_C.
+    fn lub(&self, left: &CoolType, right: &CoolType) -> CoolType {
+        if left.name() == NO_TYPE || left == right {
+            return *right;
+        }
+        self.table.ancestors(self.class_of(left)).into_iter()
+            .map(|class| CoolType::Named(class.name))
+            .find(|ancestor| self.conforms(right, ancestor))
+            .unwrap_or_else(|| cool_type(OBJECT))
+    }
+
+    fn check_class(&mut self, class: &mut ast::Class) {
+        self.class = class.name;
+        // Attributes are in scope everywhere in the class, the inherited
+        // ones included.
+        self.scope.clear();
+        self.scope.push((SELF, CoolType::SelfType));
+        for ancestor in self.table.ancestors(class.name).into_iter().rev() {
+            // An attribute called self has been reported, and mustn't hide
+            // the real one.
+            for attribute in ancestor.attributes.iter().filter(|attribute| attribute.name != SELF) {
+                let attribute_type = self.known(&attribute.cool_type);
+                self.scope.push((attribute.name, attribute_type));
+            }
+        }
+
+        for feature in &mut class.features {
+            match *feature {
+                ast::Feature::Attribute { ref name, ref cool_type, ref mut expr, span } => {
+                    let declared = self.declared(cool_type, span, format!(
+                        "Class {} of attribute {} is undefined.", cool_type, name));
+                    let init_type = self.expression(expr);
+                    if !self.conforms(&init_type, &declared) {
+                        self.error(span, format!(
+                            "Inferred type {} of initialization of attribute {} does not conform to declared type {}.",
+                            init_type, name, declared));
+                    }
+                },
+                ast::Feature::Method { ref name, ref params, ref return_type, ref mut body, span } => {
+                    let depth = self.scope.len();
+                    for param in params {
+                        if param.cool_type == CoolType::SelfType {
+                            self.error(param.span, format!(
+                                "Formal parameter {} cannot have type SELF_TYPE.", param.name));
+                        }
+                        let param_type = self.declared(&param.cool_type, param.span, format!(
+                            "Class {} of formal parameter {} is undefined.", param.cool_type, param.name));
+                        // A formal named self, or one named like an earlier formal,
+                        // has already been reported. self and the earlier formal keep
+                        // their types.
+                        let bound = self.scope[depth ..].iter().any(|&(name, _)| name == param.name);
+                        if param.name != SELF && !bound {
+                            self.scope.push((param.name, param_type));
+                        }
+                    }
+                    let declared = self.declared(return_type, span, format!(
+                        "Undefined return type {} in method {}.", return_type, name));
+                    let body_type = self.expression(body);
+                    if !self.conforms(&body_type, &declared) {
+                        self.error(span, format!(
+                            "Inferred return type {} of method {} does not conform to declared return type {}.",
+                            body_type, name, declared));
+                    }
+                    self.scope.truncate(depth);
+                }
+            }
+        }
+    }
+
+    // Works out the type of an expression and records it on the node.
+    fn expression(&mut self, expr: &mut ast::Expression) -> CoolType {
+        let span = expr.span;
+        let static_type = match expr.kind {
+            ExpressionKind::Assign(name, ref mut value) => {
+                let value_type = self.expression(value);
+                match self.lookup(name) {
+                    _ if name == SELF => self.error(span, "Cannot assign to 'self'.".to_string()),
+                    None => self.error(span, format!("Assignment to undeclared variable {}.", name)),
+                    Some(ref declared) if !self.conforms(&value_type, declared) => {
+                        self.error(span, format!(
+                            "Type {} of assigned expression does not conform to declared type {} of identifier {}.",
+                            value_type, declared, name));
+                    },
+                    Some(_) => {}
+                }
+                value_type
+            },
+            ExpressionKind::Dispatch(ref mut object, name, ref mut args) => {
+                let object_type = self.expression(object);
+                self.dispatch(span, &object_type, &object_type, name, args)
+            },
+            ExpressionKind::StaticDispatch(ref mut object, ref class, name, ref mut args) => {
+                let object_type = self.expression(object);
+                let undefined = match *class {
+                    CoolType::SelfType => Some("Static dispatch to SELF_TYPE.".to_string()),
+                    CoolType::Named(class) if !self.table.contains(class) => {
+                        Some(format!("Static dispatch to undefined class {}.", class))
+                    },
+                    CoolType::Named(_) => None
+                };
+                if let Some(message) = undefined {
+                    self.error(span, message);
+                    for arg in args.iter_mut() {
+                        self.expression(arg);
+                    }
+                    cool_type(OBJECT)
+                } else {
+                    if !self.conforms(&object_type, class) {
+                        self.error(span, format!(
+                            "Expression type {} does not conform to declared static dispatch type {}.",
+                            object_type, class));
+                    }
+                    self.dispatch(span, &object_type, class, name, args)
+                }
+            },
+            ExpressionKind::If(ref mut predicate, ref mut then_branch, ref mut else_branch) => {
+                if self.expression(predicate).name() != BOOL {
+                    self.error(span, "Predicate of 'if' does not have type Bool.".to_string());
+                }
+                let then_type = self.expression(then_branch);
+                let else_type = self.expression(else_branch);
+                self.lub(&then_type, &else_type)
+            },
+            ExpressionKind::While(ref mut condition, ref mut body) => {
+                if self.expression(condition).name() != BOOL {
+                    self.error(span, "Loop condition does not have type Bool.".to_string());
+                }
+                self.expression(body);
+                cool_type(OBJECT)
+            },
+            ExpressionKind::Let(name, ref declared, ref mut init, ref mut body) => {
+                let declared = self.declared(declared, span, format!(
+                    "Class {} of let-bound identifier {} is undefined.", declared, name));
+                if name == SELF {
+                    self.error(span, "'self' cannot be bound in a 'let' expression.".to_string());
+                }
+                let init_type = self.expression(init);
+                if !self.conforms(&init_type, &declared) {
+                    self.error(span, format!(
+                        "Inferred type {} of initialization of {} does not conform to identifier's declared type {}.",
+                        init_type, name, declared));
+                }
+                // The binding is only in scope in the body, and binding self
+                // doesn't change its type.
+                let depth = self.scope.len();
+                if name != SELF {
+                    self.scope.push((name, declared));
+                }
+                let body_type = self.expression(body);
+                self.scope.truncate(depth);
+                body_type
+            },
+            ExpressionKind::Case(ref mut scrutinee, ref mut branches) => {
+                self.expression(scrutinee);
+                let mut case_type = cool_type(NO_TYPE);
+                let mut seen = Vec::new();
+                for branch in branches {
+                    if branch.name == SELF {
+                        self.error(branch.span, "'self' bound in 'case'.".to_string());
+                    }
+                    if seen.contains(&&branch.cool_type) {
+                        self.error(branch.span, format!("Duplicate branch {} in case statement.", branch.cool_type));
+                    }
+                    seen.push(&branch.cool_type);
+                    if branch.cool_type == CoolType::SelfType {
+                        self.error(branch.span, format!(
+                            "Identifier {} declared with type SELF_TYPE in case branch.", branch.name));
+                    }
+                    let branch_type = self.declared(&branch.cool_type, branch.span, format!(
+                        "Class {} of case branch is undefined.", branch.cool_type));
+                    let depth = self.scope.len();
+                    if branch.name != SELF {
+                        self.scope.push((branch.name, branch_type));
+                    }
+                    let expr_type = self.expression(&mut branch.expr);
+                    self.scope.truncate(depth);
+                    case_type = self.lub(&case_type, &expr_type);
+                }
+                case_type
+            },
+            ExpressionKind::Block(ref mut exprs) => {
+                let mut block_type = cool_type(NO_TYPE);
+                for expr in exprs {
+                    block_type = self.expression(expr);
+                }
+                block_type
+            },
+            ExpressionKind::New(ref class) => {
+                self.declared(class, span, format!("'new' used with undefined class {}.", class))
+            },
+            ExpressionKind::IsVoid(ref mut expr) => {
+                self.expression(expr);
+                cool_type(BOOL)
+            },
+            ExpressionKind::BinaryOperation(ref op, ref mut left, ref mut right) => {
+                let left_type = self.expression(left);
+                let right_type = self.expression(right);
+                match *op {
+                    BinOp::Equal => {
+                        if (is_basic_value(&left_type) || is_basic_value(&right_type)) && left_type != right_type {
+                            self.error(span, "Illegal comparison with a basic type.".to_string());
+                        }
+                        cool_type(BOOL)
+                    },
+                    _ => {
+                        if left_type.name() != INT || right_type.name() != INT {
+                            self.error(span, format!(
+                                "non-Int arguments: {} {} {}", left_type, operator(op), right_type));
+                        }
+                        match *op {
+                            BinOp::LessThan | BinOp::LessThanEqual => cool_type(BOOL),
+                            _ => cool_type(INT)
+                        }
+                    }
+                }
+            },
+            ExpressionKind::Negation(ref mut expr) => {
+                let expr_type = self.expression(expr);
+                if expr_type.name() != INT {
+                    self.error(span, format!("Argument of '~' has type {} instead of Int.", expr_type));
+                }
+                cool_type(INT)
+            },
+            ExpressionKind::Not(ref mut expr) => {
+                let expr_type = self.expression(expr);
+                if expr_type.name() != BOOL {
+                    self.error(span, format!("Argument of 'not' has type {} instead of Bool.", expr_type));
+                }
+                cool_type(BOOL)
+            },
+            ExpressionKind::Identifier(name) => {
+                self.lookup(name).unwrap_or_else(|| {
+                    self.error(span, format!("Undeclared identifier {}.", name));
+                    cool_type(OBJECT)
+                })
+            },
+            ExpressionKind::IntLiteral(_) => cool_type(INT),
+            ExpressionKind::StringLiteral(_) => cool_type(STRING),
+            ExpressionKind::True | ExpressionKind::False => cool_type(BOOL),
+            ExpressionKind::NoExpr => cool_type(NO_TYPE)
+        };
+        expr.static_type = Some(static_type);
+        static_type
+    }
+
+    // Checks a call of `name` on an object of type `object_type`, looking
+    // the method up in `class`, and returns the type of the result.
+    fn dispatch(&mut self, span: Span, object_type: &CoolType, class: &CoolType,
+                name: Symbol, args: &mut [ast::Expression]) -> CoolType {
+        let arg_types: Vec<CoolType> = args.iter_mut().map(|arg| self.expression(arg)).collect();
+        let table = self.table;
+        let method = match table.find_method(self.class_of(class), name) {
+            Some(method) => method,
+            None => {
+                self.error(span, format!("Dispatch to undefined method {}.", name));
+                return cool_type(OBJECT);
+            }
+        };
+
+        if method.params.len() != arg_types.len() {
+            self.error(span, format!("Method {} called with wrong number of arguments.", name));
+        } else {
+            for (param, arg_type) in method.params.iter().zip(&arg_types) {
+                let param_type = self.known(&param.1);
+                if !self.conforms(arg_type, &param_type) {
+                    self.error(span, format!(
+                        "In call of method {}, type {} of parameter {} does not conform to declared type {}.",
+                        name, arg_type, param.0, param_type));
+                }
+            }
+        }
+
+        if method.return_type == CoolType::SelfType {
+            *object_type
+        } else {
+            self.known(&method.return_type)
+        }
+    }
+}
+
+// Type checks every class, recording each expression's static type.
+pub fn check_program(table: &ClassTable, program: &mut ast::Program, errors: &mut Vec<SemantError>) {
+    let mut checker = TypeChecker { table, errors, class: OBJECT, scope: Vec::new() };
+    for class in &mut program.classes {
+        checker.check_class(class);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::check_program;
+    use lexer;
+    use parser;
+    use printer;
+    use source::SourceMap;
+
+    fn check(text: &str) -> Result<String, Vec<String>> {
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.cl".to_string(), text.to_string());
+        let tokens = lexer::lex(text.chars().peekable(), file).ok().unwrap();
+        let mut program = parser::parse_cool_program(&tokens, false).ok().unwrap();
+        match check_program(&mut program) {
+            Ok(_) => {
+                let mut out = Vec::new();
+                printer::print_program(&mut out, &program, &sources).unwrap();
+                Ok(String::from_utf8(out).unwrap())
+            },
+            Err(errors) => {
+                Err(errors.iter().map(|error| format!("{}: {}", error.span.start_line, error.message)).collect())
+            }
+        }
+    }
+
+    // The types the dump gives the expressions in Main's `main`, in order.
+    fn types_in_main(text: &str) -> Vec<String> {
+        let dump = check(text).unwrap();
+        let method = dump.find("main\n").unwrap();
+        dump[method ..].lines()
+            .filter_map(|line| line.trim().strip_prefix(": "))
+            .map(|cool_type| cool_type.to_string())
+            .collect()
+    }
+
+    const ANIMALS: &str = "class Animal { speak() : String { \"...\" }; };\n\
+                           class Dog inherits Animal { fetch() : SELF_TYPE { self }; };\n\
+                           class Cat inherits Animal {};\n";
+
+    #[test]
+    fn annotates_every_expression() {
+        let main = "class Main inherits IO { main() : Object {\n\
+                    let d : Dog <- new Dog in\n\
+                    if d = d then d.fetch() else new Cat fi\n\
+                    }; };";
+        assert_eq!(types_in_main(&format!("{}{}", ANIMALS, main)),
+                   vec!["Dog", "Dog", "Dog", "Bool", "Dog", "Dog", "Cat", "Animal", "Animal"]);
+
+        let main = "class Main inherits IO { main() : Object { {\n\
+                    out_string(\"a\").out_int(1 + 2 * 3);\n\
+                    case 0 of i : Int => i < 2; s : String => s.length(); esac;\n\
+                    (new Dog)@Animal.speak();\n\
+                    while not isvoid self loop ~1 pool;\n\
+                    } }; };";
+        assert_eq!(types_in_main(&format!("{}{}", ANIMALS, main)), vec![
+            // out_string(...).out_int(...)
+            "SELF_TYPE", "String", "SELF_TYPE", "Int", "Int", "Int", "Int", "Int", "SELF_TYPE",
+            // case
+            "Int", "Int", "Int", "Bool", "String", "Int", "Object",
+            // static dispatch
+            "Dog", "String",
+            // while
+            "SELF_TYPE", "Bool", "Bool", "Int", "Int", "Object",
+            // the block
+            "Object"
+        ]);
+    }
+
+    // The errors in a Main class with these features, numbered from the
+    // line the features start on.
+    fn errors_in_main(features: &str) -> Vec<String> {
+        let text = format!("class Main inherits IO {{\n{}\nmain() : Object {{ 0 }};\n}};\n{}", features, ANIMALS);
+        check(&text).err().unwrap_or_default().into_iter()
+            .map(|error| {
+                let (line, message) = error.split_at(error.find(':').unwrap());
+                format!("{}{}", line.parse::<usize>().unwrap() - 1, message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_type_errors() {
+        let cases: &[(&str, &[&str])] = &[
+            ("x : Int <- \"a\";\ny : Thing;\nf(z : Str) : Strin { z };",
+             &["1: Inferred type String of initialization of attribute x does not conform to declared type Int.",
+               "2: Class Thing of attribute y is undefined.",
+               "3: Class Str of formal parameter z is undefined.",
+               "3: Undefined return type Strin in method f."]),
+            ("f() : Int { {\nif 1 then 2 else 3 fi;\nwhile 0 loop 0 pool;\nnot 1;\n~true;\n1 + \"a\";\n1 < true;\n1 = \"a\";\n} };",
+             &["2: Predicate of 'if' does not have type Bool.",
+               "3: Loop condition does not have type Bool.",
+               "4: Argument of 'not' has type Int instead of Bool.",
+               "5: Argument of '~' has type Bool instead of Int.",
+               "6: non-Int arguments: Int + String",
+               "7: non-Int arguments: Int < Bool",
+               "8: Illegal comparison with a basic type.",
+               "1: Inferred return type Bool of method f does not conform to declared return type Int."]),
+            ("f() : Object { {\nx;\ny <- 1;\nnew Thing;\nlet a : Int <- \"a\" in a;\nlet b : Thing in b;\n} };",
+             &["2: Undeclared identifier x.",
+               "3: Assignment to undeclared variable y.",
+               "4: 'new' used with undefined class Thing.",
+               "5: Inferred type String of initialization of a does not conform to identifier's declared type Int.",
+               "6: Class Thing of let-bound identifier b is undefined."]),
+            ("n : Int;\nf(s : String) : Object { {\nn <- s;\nf();\nf(n);\ng();\n(new Cat)@Dog.speak();\n(new Cat)@Thing.speak();\n} };",
+             &["3: Type String of assigned expression does not conform to declared type Int of identifier n.",
+               "4: Method f called with wrong number of arguments.",
+               "5: In call of method f, type Int of parameter s does not conform to declared type String.",
+               "6: Dispatch to undefined method g.",
+               "7: Expression type Cat does not conform to declared static dispatch type Dog.",
+               "8: Static dispatch to undefined class Thing."]),
+            ("f() : Object { {\nself <- new Main;\nlet self : Int in 0;\ncase 0 of\ni : Int => i;\nself : Main => 1;\nj : Int => j;\nesac;\n} };",
+             &["2: Cannot assign to 'self'.",
+               "3: 'self' cannot be bound in a 'let' expression.",
+               "6: 'self' bound in 'case'.",
+               "7: Duplicate branch Int in case statement."]),
+        ];
+        for &(features, expected) in cases {
+            assert_eq!(errors_in_main(features), expected, "checking {}", features);
+        }
+    }
+
+    #[test]
+    fn keeps_the_type_of_self_after_binding_it() {
+        assert_eq!(errors_in_main("f(self : Int) : Object { self + 1 };\n\
+                                   g() : Object { let self : Int in self + 1 };\n\
+                                   h() : Object { case 0 of self : Int => self + 1; esac };"), vec![
+            "1: 'self' cannot be the name of a formal parameter.",
+            "1: non-Int arguments: SELF_TYPE + Int",
+            "2: 'self' cannot be bound in a 'let' expression.",
+            "2: non-Int arguments: SELF_TYPE + Int",
+            "3: 'self' bound in 'case'.",
+            "3: non-Int arguments: SELF_TYPE + Int"
+        ]);
+    }
+
+    #[test]
+    fn uses_the_first_of_two_formals_with_the_same_name() {
+        assert_eq!(errors_in_main("f(a : Int, a : String) : Int { a };"), vec![
+            "1: Formal parameter a is multiply defined."
+        ]);
+    }
+
+    #[test]
+    fn types_self_type_as_the_type_of_self() {
+        let main = "class Main inherits IO { main() : Object { {\n\
+                    let s : SELF_TYPE <- copy() in if true then s else new SELF_TYPE fi;\n\
+                    if true then self else new IO fi;\n\
+                    if true then self else 1 fi;\n\
+                    (new Dog).copy();\n\
+                    } }; };";
+        assert_eq!(types_in_main(&format!("{}{}", ANIMALS, main)), vec![
+            // let s : SELF_TYPE <- copy() in ...
+            "SELF_TYPE", "SELF_TYPE", "Bool", "SELF_TYPE", "SELF_TYPE", "SELF_TYPE", "SELF_TYPE",
+            // lub(SELF_TYPE_Main, IO) and lub(SELF_TYPE_Main, Int)
+            "Bool", "SELF_TYPE", "IO", "IO",
+            "Bool", "SELF_TYPE", "Int", "Object",
+            // copy() returns the type of the object it's called on.
+            "Dog", "Dog",
+            "Dog"
+        ]);
+    }
+
+    #[test]
+    fn restricts_where_self_type_can_appear() {
+        assert_eq!(errors_in_main("s : SELF_TYPE <- self;\nm : Main <- s;\nf() : SELF_TYPE { s.copy() };"),
+                   Vec::<String>::new());
+        assert_eq!(errors_in_main("f(x : SELF_TYPE) : Object { x };\n\
+                                   g() : SELF_TYPE { new Main };\n\
+                                   h() : Object { self@SELF_TYPE.h() };\n\
+                                   k() : Object { case 0 of x : SELF_TYPE => x; esac };"), vec![
+            "1: Formal parameter x cannot have type SELF_TYPE.",
+            "2: Inferred return type Main of method g does not conform to declared return type SELF_TYPE.",
+            "3: Static dispatch to SELF_TYPE.",
+            "4: Identifier x declared with type SELF_TYPE in case branch."
+        ]);
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Source files and positions within them.
+ */
+
+use std::cmp;
+
+// Identifies one input file in a SourceMap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileId(pub usize);
+
+// A region of source text. Lines and columns both start at 1; the end
+// position points just past the last character of the region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize
+}
+
+impl Span {
+    pub fn new(file: FileId, start: (usize, usize), end: (usize, usize)) -> Span {
+        Span {
+            file,
+            start_line: start.0,
+            start_col: start.1,
+            end_line: end.0,
+            end_col: end.1
+        }
+    }
+
+    // An empty span sitting right before the start of this one.
+    pub fn empty_before(&self) -> Span {
+        Span::new(self.file,
+                  (self.start_line, self.start_col),
+                  (self.start_line, self.start_col))
+    }
+
+    // The smallest span covering both this span and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.file,
+                  (self.start_line, self.start_col),
+                  (other.end_line, other.end_col))
+    }
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub text: String
+}
+
+// All of the files given to the compiler, indexed by FileId.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: String, text: String) -> FileId {
+        self.files.push(SourceFile { name, text });
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    // The file with this name, adding one with no text if there isn't one
+    // yet. Dumped trees name the files their classes came from.
+    pub fn named(&mut self, name: &str) -> FileId {
+        match self.files.iter().position(|file| file.name == name) {
+            Some(index) => FileId(index),
+            None => self.add(name.to_string(), String::new())
+        }
+    }
+
+    // Where a span starts, as "file:line:column".
+    pub fn location(&self, span: Span) -> String {
+        format!("{}:{}:{}", self.file(span.file).name, span.start_line, span.start_col)
+    }
+
+    // The line a span starts on, followed by a line of carets underneath
+    // the part of it the span covers.
+    pub fn snippet(&self, span: Span) -> String {
+        let text = &self.file(span.file).text;
+        let line = text.lines().nth(span.start_line - 1).unwrap_or("");
+        let end_col = if span.end_line == span.start_line {
+            span.end_col
+        } else {
+            line.chars().count() + 1
+        };
+
+        // Copy tabs into the indentation so the carets line up however
+        // wide the terminal draws them.
+        let indent: String = line.chars()
+            .take(span.start_col - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let carets = "^".repeat(cmp::max(end_col.saturating_sub(span.start_col), 1));
+        format!("{}\n{}{}", line, indent, carets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(text: &str) -> (SourceMap, FileId) {
+        let mut sources = SourceMap::new();
+        sources.add("other.cl".to_string(), String::new());
+        let file = sources.add("dir/a.cl".to_string(), text.to_string());
+        (sources, file)
+    }
+
+    #[test]
+    fn locates_spans_in_their_file() {
+        let (sources, file) = sources("class A {\n  x : Int;\n};\n");
+        let span = Span::new(file, (2, 3), (2, 10));
+        assert_eq!(sources.location(span), "dir/a.cl:2:3");
+        assert_eq!(sources.location(span.empty_before()), "dir/a.cl:2:3");
+        assert_eq!(span.empty_before(), Span::new(file, (2, 3), (2, 3)));
+        assert_eq!(Span::new(file, (1, 1), (1, 6)).to(span), Span::new(file, (1, 1), (2, 10)));
+    }
+
+    #[test]
+    fn underlines_spans_with_carets() {
+        let (sources, file) = sources("class A {\n\tx : Int <- \"a\n\";\n};");
+        assert_eq!(sources.snippet(Span::new(file, (1, 7), (1, 8))), "class A {\n      ^");
+        // Tabs are kept so the carets line up with the text.
+        assert_eq!(sources.snippet(Span::new(file, (2, 6), (2, 9))), "\tx : Int <- \"a\n\t    ^^^");
+        // A span running onto later lines is underlined to the end of its
+        // first line, counting the newline.
+        assert_eq!(sources.snippet(Span::new(file, (2, 12), (3, 2))), "\tx : Int <- \"a\n\t          ^^^");
+        // An empty span still gets a caret, even past the end of the file.
+        assert_eq!(sources.snippet(Span::new(file, (4, 3), (4, 3))), "};\n  ^");
+        assert_eq!(sources.snippet(Span::new(file, (5, 1), (5, 1))), "\n^");
+    }
+}
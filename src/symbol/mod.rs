@@ -0,0 +1,193 @@
+/*
+ * Interned strings. Like the reference compiler, we keep every name and
+ * constant in a program once, in one of three global tables: identifiers
+ * and type names, integer constants (as their digits) and string
+ * constants. A Symbol is a small copyable id into one of them, so
+ * comparing two names compares two integers, and the code generator can
+ * walk the int and string tables to emit the program's constants.
+ *
+ * The names the compiler itself needs to look for are interned up front,
+ * in the order of PREDEFINED, so they can be used as constants.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Table {
+    Id,
+    Int,
+    Str
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    table: Table,
+    index: u32
+}
+
+const fn predefined(index: u32) -> Symbol {
+    Symbol { table: Table::Id, index }
+}
+
+pub const OBJECT: Symbol = predefined(0);
+pub const IO: Symbol = predefined(1);
+pub const INT: Symbol = predefined(2);
+pub const STRING: Symbol = predefined(3);
+pub const BOOL: Symbol = predefined(4);
+pub const SELF_TYPE: Symbol = predefined(5);
+pub const MAIN: Symbol = predefined(6);
+pub const MAIN_METHOD: Symbol = predefined(7);
+pub const SELF: Symbol = predefined(8);
+// The type of an absent expression, which conforms to every type.
+pub const NO_TYPE: Symbol = predefined(9);
+
+const PREDEFINED: &[&str] = &[
+    "Object", "IO", "Int", "String", "Bool", "SELF_TYPE", "Main", "main", "self", "_no_type"
+];
+
+// One table: its strings in the order they were added, and where to find
+// each of them. Interned strings live as long as the compiler does.
+#[derive(Default)]
+struct Interner {
+    strings: Vec<&'static str>,
+    indices: HashMap<&'static str, u32>
+}
+
+impl Interner {
+    fn intern(&mut self, text: &str) -> u32 {
+        if let Some(&index) = self.indices.get(text) {
+            return index;
+        }
+        let text: &'static str = Box::leak(text.to_string().into_boxed_str());
+        let index = self.strings.len() as u32;
+        self.strings.push(text);
+        self.indices.insert(text, index);
+        index
+    }
+}
+
+struct Tables {
+    ids: Interner,
+    ints: Interner,
+    strings: Interner
+}
+
+impl Tables {
+    fn get(&mut self, table: Table) -> &mut Interner {
+        match table {
+            Table::Id => &mut self.ids,
+            Table::Int => &mut self.ints,
+            Table::Str => &mut self.strings
+        }
+    }
+}
+
+fn with_tables<T, F: FnOnce(&mut Tables) -> T>(f: F) -> T {
+    static TABLES: OnceLock<Mutex<Tables>> = OnceLock::new();
+    let tables = TABLES.get_or_init(|| {
+        let mut ids = Interner::default();
+        for name in PREDEFINED {
+            ids.intern(name);
+        }
+        Mutex::new(Tables { ids, ints: Interner::default(), strings: Interner::default() })
+    });
+    f(&mut tables.lock().unwrap())
+}
+
+impl Symbol {
+    fn intern(table: Table, text: &str) -> Symbol {
+        let index = with_tables(|tables| tables.get(table).intern(text));
+        Symbol { table, index }
+    }
+
+    // An identifier or type name.
+    pub fn id(name: &str) -> Symbol {
+        Symbol::intern(Table::Id, name)
+    }
+
+    // An integer constant, kept as the digits it was written with.
+    pub fn int(digits: &str) -> Symbol {
+        Symbol::intern(Table::Int, digits)
+    }
+
+    // A string constant, with its escapes already processed.
+    pub fn string(text: &str) -> Symbol {
+        Symbol::intern(Table::Str, text)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        with_tables(|tables| tables.get(self.table).strings[self.index as usize])
+    }
+
+    // Where the symbol is in its table, counting from 0.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+impl Table {
+    // Every symbol in the table, in the order they were added.
+    pub fn entries(self) -> Vec<Symbol> {
+        let count = with_tables(|tables| tables.get(self).strings.len());
+        (0 .. count as u32).map(|index| Symbol { table: self, index }).collect()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interns_each_string_once_per_table() {
+        let name = Symbol::id("interned_once");
+        assert_eq!(Symbol::id("interned_once"), name);
+        assert_eq!(name.as_str(), "interned_once");
+
+        // The same text in another table is a different symbol.
+        let string = Symbol::string("interned_once");
+        assert_ne!(string, name);
+        assert_eq!(string.as_str(), "interned_once");
+        let int = Symbol::int("007");
+        assert_eq!(int.as_str(), "007");
+        assert_ne!(int, Symbol::string("007"));
+    }
+
+    #[test]
+    fn lists_each_table_in_the_order_it_was_filled() {
+        let first = Symbol::string("listed first");
+        let second = Symbol::string("listed second");
+        let strings = Table::Str.entries();
+        assert_eq!(strings[first.index()], first);
+        assert_eq!(strings[second.index()], second);
+        assert!(first.index() < second.index());
+        assert!(!strings.contains(&Symbol::id("listed first")));
+
+        let ids = Table::Id.entries();
+        assert_eq!(&ids[.. PREDEFINED.len()], &[OBJECT, IO, INT, STRING, BOOL, SELF_TYPE, MAIN, MAIN_METHOD, SELF, NO_TYPE]);
+        let int = Symbol::int("42");
+        assert!(Table::Int.entries().contains(&int));
+    }
+
+    #[test]
+    fn predefines_the_names_the_compiler_uses() {
+        for (i, name) in PREDEFINED.iter().enumerate() {
+            assert_eq!(Symbol::id(name), predefined(i as u32));
+        }
+        assert_eq!(SELF_TYPE.to_string(), "SELF_TYPE");
+        assert_eq!(NO_TYPE.as_str(), "_no_type");
+    }
+}
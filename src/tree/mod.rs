@@ -0,0 +1,520 @@
+/*
+ * Reads program trees in the indented format the reference parser
+ * prints, which is also what printer writes, so that later phases can be
+ * run on trees the reference parser produced.
+ *
+ * The reference parser puts a `#<line>` line before each node, the file
+ * name after each class's parent, and a `: <type>` line after each
+ * expression. We take the line numbers and file names for the nodes'
+ * spans and skip the types; none of them have to be there.
+ */
+
+use ast;
+use source::{FileId, SourceMap, Span};
+use symbol::Symbol;
+
+// One line of the tree, without its indentation.
+struct Line<'a> {
+    indent: usize,
+    text: &'a str,
+    // The line of the source the node on this line came from, if a #n
+    // line said so.
+    source_line: Option<usize>,
+    // Where this line is in the tree, for error messages.
+    number: usize
+}
+
+struct Reader<'a, 'b> {
+    lines: Vec<Line<'a>>,
+    position: usize,
+    // The file the nodes we're reading came from, and where to look up
+    // the ones classes name.
+    file: FileId,
+    sources: &'b mut SourceMap,
+    // The source line of the node we're reading, which nodes without a
+    // line of their own inherit.
+    source_line: usize
+}
+
+// Whether `text` looks like a tree rather than Cool source: Cool names
+// can't start with an underscore, so no Cool program starts `_program`.
+pub fn is_tree(text: &str) -> bool {
+    text.lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        == Some("_program")
+}
+
+// Reads the tree in `text`, from `file`. A class that names the file it
+// came from gets spans in that file, which is added to `sources` if need be.
+pub fn read_program(text: &str, file: FileId, sources: &mut SourceMap) -> Result<ast::Program, String> {
+    let mut lines = Vec::new();
+    let mut source_line = None;
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with(':') {
+            continue;
+        }
+        if let Some(number) = trimmed.strip_prefix('#') {
+            match number.trim().parse() {
+                Ok(number) => source_line = Some(number),
+                Err(_) => return Err(format!("line {}: bad line number '{}'", i + 1, trimmed))
+            }
+            continue;
+        }
+        lines.push(Line {
+            indent: line.len() - trimmed.len(),
+            text: trimmed.trim_end(),
+            source_line: source_line.take(),
+            number: i + 1
+        });
+    }
+
+    let mut reader = Reader { lines, position: 0, file, sources, source_line: 1 };
+    let program = reader.program()?;
+    if let Some(line) = reader.lines.get(reader.position) {
+        return Err(format!("line {}: unexpected '{}' after the program", line.number, line.text));
+    }
+    Ok(program)
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error<T>(&self, expected: &str) -> Result<T, String> {
+        match self.lines.get(self.position) {
+            Some(line) => Err(format!("line {}: expected {} but found '{}'", line.number, expected, line.text)),
+            None => Err(format!("expected {} but the tree ended", expected))
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.lines.get(self.position).map(|line| line.text)
+    }
+
+    // Takes the next line, which should be `text`.
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        if self.peek() == Some(text) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("'{}'", text))
+        }
+    }
+
+    // Takes the next line, which names a node of one of the given sorts,
+    // and returns its name and the span it stands for.
+    fn node(&mut self, expected: &str) -> Result<(&'a str, Span), String> {
+        let line = match self.lines.get(self.position) {
+            Some(line) if line.text.starts_with('_') => line,
+            _ => return self.error(expected)
+        };
+        self.position += 1;
+        if let Some(source_line) = line.source_line {
+            self.source_line = source_line;
+        }
+        let position = (self.source_line, 1);
+        Ok((line.text, Span::new(self.file, position, position)))
+    }
+
+    // Takes the next line, which is a name or a type.
+    fn symbol(&mut self, expected: &str) -> Result<ast::Symbol, String> {
+        match self.peek() {
+            Some(text) if !text.starts_with('_') && !text.starts_with('"') && text != "(" && text != ")" => {
+                self.position += 1;
+                Ok(Symbol::id(text))
+            },
+            _ => self.error(expected)
+        }
+    }
+
+    fn cool_type(&mut self, expected: &str) -> Result<ast::CoolType, String> {
+        self.symbol(expected).map(ast::CoolType::new)
+    }
+
+    // Whether the line after the one at `parent` is one of its children.
+    fn has_child(&self, parent: usize) -> bool {
+        match self.lines.get(self.position) {
+            Some(line) => line.indent > self.lines[parent].indent,
+            None => false
+        }
+    }
+
+    fn program(&mut self) -> Result<ast::Program, String> {
+        let (_, span) = self.node("'_program'")?;
+        if self.lines[self.position - 1].text != "_program" {
+            self.position -= 1;
+            return self.error("'_program'");
+        }
+        let mut classes = Vec::new();
+        while self.peek().is_some() {
+            classes.push(self.class()?);
+        }
+        Ok(ast::Program { classes, span })
+    }
+
+    fn class(&mut self) -> Result<ast::Class, String> {
+        let (kind, mut span) = self.node("'_class'")?;
+        if kind != "_class" {
+            self.position -= 1;
+            return self.error("'_class'");
+        }
+        let name = self.symbol("a class name")?;
+        let parent = self.symbol("a parent class name")?;
+        // The file the class came from, which the spans of its nodes are in.
+        let outer_file = self.file;
+        if self.peek().is_some_and(|text| text.starts_with('"')) {
+            let file_name = self.string()?;
+            self.file = self.sources.named(&file_name);
+            span.file = self.file;
+        }
+        self.expect("(")?;
+        let mut features = Vec::new();
+        while self.peek() != Some(")") {
+            features.push(self.feature()?);
+        }
+        self.expect(")")?;
+        self.file = outer_file;
+        Ok(ast::Class { name, parent, features, span })
+    }
+
+    fn feature(&mut self) -> Result<ast::Feature, String> {
+        match self.node("a feature")? {
+            ("_method", span) => {
+                let name = self.symbol("a method name")?;
+                let mut params = Vec::new();
+                while self.peek() == Some("_formal") {
+                    let (_, span) = self.node("'_formal'")?;
+                    let name = self.symbol("a parameter name")?;
+                    let cool_type = self.cool_type("a parameter type")?;
+                    params.push(ast::Formal { name, cool_type, span });
+                }
+                let return_type = self.cool_type("a return type")?;
+                let body = self.boxed_expression()?;
+                Ok(ast::Feature::Method { name, params, return_type, body, span })
+            },
+            ("_attr", span) => {
+                let name = self.symbol("an attribute name")?;
+                let cool_type = self.cool_type("an attribute type")?;
+                let expr = self.boxed_expression()?;
+                Ok(ast::Feature::Attribute { name, cool_type, expr, span })
+            },
+            _ => {
+                self.position -= 1;
+                self.error("a feature")
+            }
+        }
+    }
+
+    fn boxed_expression(&mut self) -> Result<Box<ast::Expression>, String> {
+        self.expression().map(Box::new)
+    }
+
+    // The arguments of a dispatch, in parentheses.
+    fn args(&mut self) -> Result<Vec<ast::Expression>, String> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while self.peek() != Some(")") {
+            args.push(self.expression()?);
+        }
+        self.expect(")")?;
+        Ok(args)
+    }
+
+    fn expression(&mut self) -> Result<ast::Expression, String> {
+        let start = self.position;
+        // Children of this node inherit its line, not their siblings'.
+        let parent_line = self.source_line;
+        let (kind, span) = self.node("an expression")?;
+        let kind = match kind {
+            "_assign" => ast::ExpressionKind::Assign(self.symbol("a name")?, self.boxed_expression()?),
+            "_dispatch" => {
+                let object = self.boxed_expression()?;
+                let method = self.symbol("a method name")?;
+                ast::ExpressionKind::Dispatch(object, method, self.args()?)
+            },
+            "_static_dispatch" => {
+                let object = self.boxed_expression()?;
+                let static_type = self.cool_type("a type")?;
+                let method = self.symbol("a method name")?;
+                ast::ExpressionKind::StaticDispatch(object, static_type, method, self.args()?)
+            },
+            "_cond" => ast::ExpressionKind::If(
+                self.boxed_expression()?,
+                self.boxed_expression()?,
+                self.boxed_expression()?),
+            "_loop" => ast::ExpressionKind::While(self.boxed_expression()?, self.boxed_expression()?),
+            "_let" => ast::ExpressionKind::Let(
+                self.symbol("a name")?,
+                self.cool_type("a type")?,
+                self.boxed_expression()?,
+                self.boxed_expression()?),
+            "_typcase" => {
+                let value = self.boxed_expression()?;
+                let mut branches = Vec::new();
+                while self.has_child(start) && self.peek() == Some("_branch") {
+                    let (_, span) = self.node("'_branch'")?;
+                    let name = self.symbol("a name")?;
+                    let cool_type = self.cool_type("a type")?;
+                    let expr = self.boxed_expression()?;
+                    branches.push(ast::CaseBranch { name, cool_type, expr, span });
+                }
+                ast::ExpressionKind::Case(value, branches)
+            },
+            "_block" => {
+                let mut statements = Vec::new();
+                while self.has_child(start) {
+                    statements.push(self.expression()?);
+                }
+                ast::ExpressionKind::Block(statements)
+            },
+            "_new" => ast::ExpressionKind::New(self.cool_type("a type")?),
+            "_isvoid" => ast::ExpressionKind::IsVoid(self.boxed_expression()?),
+            "_plus" | "_sub" | "_mul" | "_divide" | "_lt" | "_leq" | "_eq" => {
+                let op = match kind {
+                    "_plus" => ast::BinOp::Plus,
+                    "_sub" => ast::BinOp::Minus,
+                    "_mul" => ast::BinOp::Mult,
+                    "_divide" => ast::BinOp::Divide,
+                    "_lt" => ast::BinOp::LessThan,
+                    "_leq" => ast::BinOp::LessThanEqual,
+                    _ => ast::BinOp::Equal
+                };
+                ast::ExpressionKind::BinaryOperation(op, self.boxed_expression()?, self.boxed_expression()?)
+            },
+            "_neg" => ast::ExpressionKind::Negation(self.boxed_expression()?),
+            "_comp" => ast::ExpressionKind::Not(self.boxed_expression()?),
+            "_object" => ast::ExpressionKind::Identifier(self.symbol("a name")?),
+            "_int" => match self.peek() {
+                Some(digits) if digits.parse::<i32>().is_ok() => {
+                    self.position += 1;
+                    ast::ExpressionKind::IntLiteral(Symbol::int(digits))
+                },
+                _ => return self.error("an integer")
+            },
+            "_string" => ast::ExpressionKind::StringLiteral(Symbol::string(&self.string()?)),
+            "_bool" => match self.peek() {
+                Some("1") => {
+                    self.position += 1;
+                    ast::ExpressionKind::True
+                },
+                Some("0") => {
+                    self.position += 1;
+                    ast::ExpressionKind::False
+                },
+                _ => return self.error("1 or 0")
+            },
+            "_no_expr" => ast::ExpressionKind::NoExpr,
+            _ => {
+                self.position = start;
+                return self.error("an expression");
+            }
+        };
+        self.source_line = parent_line;
+        Ok(ast::Expression::new(kind, span))
+    }
+
+    // A string constant in quotes, with the escapes printer::escape_string
+    // writes.
+    fn string(&mut self) -> Result<String, String> {
+        let text = match self.peek() {
+            Some(text) if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') => text,
+            _ => return self.error("a string in quotes")
+        };
+        let inner = &text.as_bytes()[1 .. text.len() - 1];
+        let mut bytes = Vec::with_capacity(inner.len());
+        let mut i = 0;
+        while i < inner.len() {
+            if inner[i] != b'\\' {
+                bytes.push(inner[i]);
+                i += 1;
+                continue;
+            }
+            let escape = inner.get(i + 1).cloned();
+            i += 2;
+            match escape {
+                Some(b'n') => bytes.push(b'\n'),
+                Some(b't') => bytes.push(b'\t'),
+                Some(b'b') => bytes.push(0x08),
+                Some(b'f') => bytes.push(0x0c),
+                Some(b'\\') => bytes.push(b'\\'),
+                Some(b'"') => bytes.push(b'"'),
+                Some(b'0' ..= b'7') => {
+                    let octal = inner.get(i - 1 .. i + 2)
+                        .and_then(|digits| ::std::str::from_utf8(digits).ok())
+                        .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+                    match octal {
+                        Some(byte) => bytes.push(byte),
+                        None => return self.error("a string with valid escapes")
+                    }
+                    i += 2;
+                },
+                _ => return self.error("a string with valid escapes")
+            }
+        }
+        self.position += 1;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer;
+    use parser;
+    use printer;
+
+    // A synthetic fixture in the reference parser's format: the tree it
+    // prints for SOURCE, with the line numbers it would give each node and
+    // the `A` in the string written as the octal escape it may use instead.
+    const REFERENCE: &str = r#"#8
+_program
+  #8
+  _class
+    Main
+    IO
+    "main.cl"
+    (
+    #2
+    _attr
+      count
+      Int
+      #2
+      _int
+        0
+      : _no_type
+    #3
+    _attr
+      name
+      String
+      #3
+      _no_expr
+      : _no_type
+    #7
+    _method
+      main
+      #4
+      _formal
+        n
+        Int
+      SELF_TYPE
+      #7
+      _block
+        #5
+        _dispatch
+          #5
+          _object
+            self
+          : _no_type
+          out_string
+          (
+          #5
+          _string
+            "tab\there\n\"quoted\"\\ \101"
+          : _no_type
+          )
+        : _no_type
+        #6
+        _typcase
+          #6
+          _object
+            n
+          : _no_type
+          #6
+          _branch
+            i
+            Int
+            #6
+            _leq
+              #6
+              _neg
+                #6
+                _object
+                  i
+                : _no_type
+              : _no_type
+              #6
+              _bool
+                1
+              : _no_type
+            : _no_type
+          #6
+          _branch
+            o
+            Object
+            #6
+            _int
+              0
+            : _no_type
+        : _no_type
+      : _no_type
+    )
+"#;
+
+    const SOURCE: &str = "class Main inherits IO {
+        count : Int <- 0;
+        name : String;
+        main(n : Int) : SELF_TYPE { {
+            out_string(\"tab\\there\\n\\\"quoted\\\"\\\\ A\");
+            case n of i : Int => ~i <= true; o : Object => 0; esac;
+        } };
+    };";
+
+    #[test]
+    fn reads_trees_in_the_reference_format() {
+        let program = read_program(REFERENCE, FileId(0), &mut SourceMap::new()).unwrap();
+        let mut sources = SourceMap::new();
+        let file = sources.add("main.cl".to_string(), SOURCE.to_string());
+        let tokens = lexer::lex(SOURCE.chars().peekable(), file).ok().unwrap();
+        let parsed = parser::parse_cool_program(&tokens, false).ok().unwrap();
+        assert_eq!(ast::without_spans(&program), ast::without_spans(&parsed));
+
+        // Apart from the escape, the fixture is what we print for SOURCE.
+        let mut out = Vec::new();
+        printer::print_program(&mut out, &parsed, &sources).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), REFERENCE.replace("\\101", "A"));
+        assert!(is_tree(REFERENCE));
+        assert!(!is_tree(SOURCE));
+    }
+
+    #[test]
+    fn takes_spans_from_line_numbers() {
+        let mut sources = SourceMap::new();
+        let tree = sources.add("main.tree".to_string(), REFERENCE.to_string());
+        let program = read_program(REFERENCE, tree, &mut sources).unwrap();
+        assert_eq!(program.span.file, tree);
+        // The class and everything in it are in the file the tree names.
+        let class = &program.classes[0];
+        let file = sources.named("main.cl");
+        assert_ne!(file, tree);
+        assert_eq!(class.span, Span::new(file, (8, 1), (8, 1)));
+        let body = match class.features[2] {
+            ast::Feature::Method { ref body, .. } => body,
+            _ => unreachable!()
+        };
+        assert_eq!(body.span.start_line, 7);
+        assert_eq!(body.span.file, file);
+        match body.kind {
+            ast::ExpressionKind::Block(ref statements) => {
+                assert_eq!(statements[0].span.start_line, 5);
+                assert_eq!(statements[1].span.start_line, 6);
+            },
+            _ => panic!("expected a block, not {:?}", body.kind)
+        }
+    }
+
+    #[test]
+    fn reports_malformed_trees() {
+        let cases = [
+            ("_class\n", "line 1: expected '_program' but found '_class'"),
+            ("_program\n  _class\n    A\n", "expected a parent class name but the tree ended"),
+            ("_program\n  _class\n    A\n    Object\n    (\n    _attr\n      x\n      Int\n      _frob\n    )\n",
+             "line 9: expected an expression but found '_frob'"),
+            ("_program\n  _class\n    A\n    Object\n    (\n    _attr\n      x\n      Int\n      _int\n        x1\n    )\n",
+             "line 10: expected an integer but found 'x1'"),
+            ("#x\n_program\n", "line 1: bad line number '#x'"),
+            ("_program\n)\n", "line 2: expected '_class' but found ')'"),
+        ];
+        for &(text, expected) in &cases {
+            assert_eq!(read_program(text, FileId(0), &mut SourceMap::new()).err(), Some(expected.to_string()), "reading {:?}", text);
+        }
+    }
+}
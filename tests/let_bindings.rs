@@ -0,0 +1,128 @@
+// The tree dumped for a let with several bindings, which the parser
+// turns into nested single-binding lets.
+
+use std::env;
+use std::fs;
+use std::process::Command;
+
+// Dumps the tree for a program whose only method returns `expr`.
+fn dump(name: &str, expr: &str) -> String {
+    let path = env::temp_dir().join(format!("cool-let-{}-{}.cl", name, std::process::id()));
+    fs::write(&path, format!("class Main {{ main() : Object {{ {} }}; }};\n", expr)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool")).arg("--parse").arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // Everything below the method's return type.
+    let tree = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = tree.lines()
+        .skip_while(|line| *line != "      Object")
+        .skip(1)
+        .take_while(|line| *line != "    )")
+        .collect();
+    lines.join("\n") + "\n"
+}
+
+// What `dump` should print for `let` with `count` bindings of x0, x1, ...
+// with body `x0`, each initialized to its index if `initialized`.
+fn nested(count: usize, initialized: bool) -> String {
+    let mut tree = String::new();
+    for i in 0 .. count {
+        let indent = "  ".repeat(i + 3);
+        tree.push_str(&format!("{}#1\n{}_let\n{}  x{}\n{}  Int\n", indent, indent, indent, i, indent));
+        if initialized {
+            tree.push_str(&format!("{}  #1\n{}  _int\n{}    {}\n", indent, indent, indent, i));
+        } else {
+            tree.push_str(&format!("{}  #1\n{}  _no_expr\n", indent, indent));
+        }
+        tree.push_str(&format!("{}  : _no_type\n", indent));
+    }
+    let indent = "  ".repeat(count + 3);
+    tree.push_str(&format!("{}#1\n{}_object\n{}  x0\n{}: _no_type\n", indent, indent, indent, indent));
+    // Each let's type comes after its body.
+    for i in (0 .. count).rev() {
+        tree.push_str(&format!("{}: _no_type\n", "  ".repeat(i + 3)));
+    }
+    tree
+}
+
+fn bindings(count: usize, initialized: bool) -> String {
+    let bindings: Vec<String> = (0 .. count).map(|i| {
+        if initialized { format!("x{} : Int <- {}", i, i) } else { format!("x{} : Int", i) }
+    }).collect();
+    format!("let {} in x0", bindings.join(", "))
+}
+
+#[test]
+fn prints_a_single_binding() {
+    assert_eq!(dump("one", "let x0 : Int in x0"), [
+        "      #1",
+        "      _let",
+        "        x0",
+        "        Int",
+        "        #1",
+        "        _no_expr",
+        "        : _no_type",
+        "        #1",
+        "        _object",
+        "          x0",
+        "        : _no_type",
+        "      : _no_type",
+        ""
+    ].join("\n"));
+    assert_eq!(dump("one-initialized", "let x0 : Int <- 0 in x0"), [
+        "      #1",
+        "      _let",
+        "        x0",
+        "        Int",
+        "        #1",
+        "        _int",
+        "          0",
+        "        : _no_type",
+        "        #1",
+        "        _object",
+        "          x0",
+        "        : _no_type",
+        "      : _no_type",
+        ""
+    ].join("\n"));
+}
+
+#[test]
+fn nests_two_bindings() {
+    assert_eq!(dump("two", "let x0 : Int, x1 : Int <- 1 in x0"), [
+        "      #1",
+        "      _let",
+        "        x0",
+        "        Int",
+        "        #1",
+        "        _no_expr",
+        "        : _no_type",
+        "        #1",
+        "        _let",
+        "          x1",
+        "          Int",
+        "          #1",
+        "          _int",
+        "            1",
+        "          : _no_type",
+        "          #1",
+        "          _object",
+        "            x0",
+        "          : _no_type",
+        "        : _no_type",
+        "      : _no_type",
+        ""
+    ].join("\n"));
+}
+
+#[test]
+fn nests_any_number_of_bindings() {
+    for &count in &[1, 2, 10] {
+        for &initialized in &[false, true] {
+            let name = format!("{}-{}", count, initialized);
+            assert_eq!(dump(&name, &bindings(count, initialized)), nested(count, initialized),
+                       "{}", bindings(count, initialized));
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// Trees dumped with --parse read back in as the same program.
+
+use std::env;
+use std::fs;
+use std::process::Command;
+
+fn cool(path: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_cool")).arg("--parse").arg(path).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn reads_back_the_dumps_of_the_example_programs() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let dump = cool(path.to_str().unwrap());
+
+        let tree = env::temp_dir().join(format!("cool-tree-{}-{}", std::process::id(),
+                                               path.file_stem().unwrap().to_str().unwrap()));
+        fs::write(&tree, &dump).unwrap();
+        let reread = cool(tree.to_str().unwrap());
+        fs::remove_file(&tree).unwrap();
+        assert_eq!(reread, dump, "{:?} read back differently", path);
+    }
+}